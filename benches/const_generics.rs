@@ -1,6 +1,7 @@
 #![feature(test)]
 #![deny(unused)]
 #![warn(clippy::all)]
+#![allow(clippy::extra_unused_type_parameters)]
 
 extern crate test;
 
@@ -12,7 +13,7 @@ mod benches {
     #[bench]
     fn fill_vec<const LEN: usize>(b: &mut Bencher) {
         b.iter(|| {
-            let v: Vec<u8> = iter::repeat(0xA5).take(LEN).collect();
+            let v: Vec<u8> = iter::repeat_n(0xA5, LEN).collect();
             test::black_box(v);
         })
     }
@@ -1,49 +1,444 @@
 use crate::error::ErrorRecord;
-use crate::extract::{InstArguments, TestFn, Tests};
-use crate::options::MacroOpts;
+use crate::external;
+use crate::extract::{HookKind, TestFn, Tests, TraitSuite};
+use crate::instantiation::{InstArguments, InstAttrArgs, InstMatrix, InstSpec, ResolvedArgs};
+use crate::module_file;
+use crate::naming;
+use crate::options::{InstOpts, MacroOpts};
+use crate::signature::{GenericParamSubst, TestGenericParam, TestGenericParamKind};
 
 use proc_macro2::TokenStream;
-use quote::ToTokens;
+use quote::{format_ident, quote, ToTokens};
 use syn::punctuated::Punctuated;
+use syn::token::Brace;
 use syn::visit_mut::{self, VisitMut};
 use syn::{parse_quote, Token};
-use syn::{Error, Expr, Item, ItemMod, Path};
+use syn::{
+    Error, Expr, FieldValue, Fields, FnArg, GenericArgument, Ident, Item, ItemMod, Lifetime, Path,
+    PathSegment, ReturnType, Stmt, Type, TypePath, UseTree, WhereClause,
+};
 
-pub fn expand(opts: &MacroOpts, mut ast: ItemMod) -> TokenStream {
-    match transform(opts, &mut ast) {
+use std::collections::{BTreeMap, HashSet};
+use std::mem;
+
+pub fn expand(opts: &MacroOpts, args: TokenStream, mut ast: ItemMod) -> TokenStream {
+    let result = if opts.includes.is_empty() {
+        transform(opts, &mut ast)
+    } else {
+        external::request_includes(opts, args, &mut ast)
+    };
+    match result {
         Ok(()) => ast.into_token_stream(),
         Err(e) => e.to_compile_error(),
     }
 }
 
-fn transform(opts: &MacroOpts, ast: &mut ItemMod) -> syn::Result<()> {
+pub fn transform(opts: &MacroOpts, ast: &mut ItemMod) -> syn::Result<()> {
+    if ast.content.is_none() {
+        module_file::load(ast)?;
+    }
+    let ident = ast.ident.clone();
     let (tests, items) = Tests::try_extract(opts, ast)?;
-    instantiate(tests, items)
+    let companion = external::suite_companion(opts, &ident, &tests);
+    instantiate(opts, tests, items)?;
+    items.extend(companion);
+    Ok(())
 }
 
-fn instantiate(tests: Tests, items: &mut [Item]) -> syn::Result<()> {
-    let mut instantiator = Instantiator {
-        tests,
-        depth: 1,
-        errors: Default::default(),
-    };
+fn instantiate(opts: &MacroOpts, mut tests: Tests, items: &mut Vec<Item>) -> syn::Result<()> {
+    let trait_suites = mem::take(&mut tests.trait_suites);
+    let mut instantiator = Instantiator::new(opts, tests, None);
+    if !trait_suites.is_empty() {
+        instantiate_trait_suites(&trait_suites, items, &mut instantiator.errors);
+    }
     for item in items.iter_mut() {
         instantiator.visit_item_mut(item);
     }
+    for inst in &opts.instantiations {
+        let name = match inst.mod_name() {
+            Ok(name) => name,
+            Err(e) => {
+                instantiator.errors.add_error(e);
+                continue;
+            }
+        };
+        if declares_type_name(items, &name) {
+            let msg = format!("duplicate module name `{}` for instantiation", name);
+            let e = match &inst.name {
+                Some(name) => Error::new_spanned(name, msg),
+                None => Error::new_spanned(&inst.spec, msg),
+            };
+            instantiator.errors.add_error(e);
+            continue;
+        }
+        let mut content = Vec::new();
+        instantiator.instantiate_spec(&inst.spec, &inst.opts, &mut content);
+        let attrs = inst.opts.mod_attrs();
+        items.push(parse_quote! {
+            #(#attrs)*
+            mod #name {
+                #(#content)*
+            }
+        });
+    }
     instantiator.errors.check()?;
     Ok(())
 }
 
-fn shim_mod(test: &TestFn, inst_args: &InstArguments, root_path: &Path) -> Item {
-    let mod_call_sig = call_sig_mod(test, root_path);
+// Populates an instantiation module outside of the suite module.
+// The suite module is referred to by `suite_alias` in the scope
+// enclosing the instantiation module.
+pub fn instantiate_external(
+    opts: &MacroOpts,
+    tests: Tests,
+    suite_alias: Ident,
+    item: &mut ItemMod,
+) -> syn::Result<()> {
+    let mut instantiator = Instantiator::new(opts, tests, Some(suite_alias));
+    instantiator.visit_item_mod_mut(item);
+    instantiator.errors.check()
+}
+
+// Adds a test module after each implementation of a suite trait found in
+// `items` or nested modules, with test functions calling the test methods
+// as implemented for the type.
+// Checks if the path names the trait declared in the module
+// as `Name` or `self::Name`, rather than a trait elsewhere
+// that has the same name
+fn names_local_trait(path: &Path, ident: &Ident) -> bool {
+    if path.leading_colon.is_some() {
+        return false;
+    }
+    let mut segments = path.segments.iter();
+    let first = segments.next().unwrap();
+    let last = if first.ident == "self" {
+        match segments.next() {
+            Some(segment) => segment,
+            None => return false,
+        }
+    } else {
+        first
+    };
+    segments.next().is_none() && last.ident == *ident
+}
+
+fn instantiate_trait_suites(
+    suites: &[TraitSuite],
+    items: &mut Vec<Item>,
+    errors: &mut ErrorRecord,
+) {
+    let mut generated = Vec::new();
+    let mut names = HashSet::new();
+    let declared = items
+        .iter()
+        .flat_map(declared_names)
+        .filter(|(_, ns)| *ns == Namespace::Type)
+        .map(|(name, _)| name)
+        .collect::<HashSet<_>>();
+    for (pos, item) in items.iter_mut().enumerate() {
+        match item {
+            Item::Impl(item) => {
+                let trait_path = match &item.trait_ {
+                    Some((None, path, _)) => path,
+                    _ => continue,
+                };
+                let suite = match suites
+                    .iter()
+                    .find(|suite| names_local_trait(trait_path, &suite.ident))
+                {
+                    Some(suite) => suite,
+                    None => continue,
+                };
+                if !item.generics.params.is_empty() {
+                    errors.add_error(Error::new_spanned(
+                        &item.generics,
+                        "implementations of a suite trait cannot have generic parameters",
+                    ));
+                    continue;
+                }
+                // Named after both the type and the trait, as a type
+                // can implement several suite traits
+                let name = match (
+                    naming::derive_mod_name(&item.self_ty),
+                    naming::derive_mod_name(&suite.ident),
+                ) {
+                    (Ok(type_name), Ok(trait_name)) => {
+                        format_ident!("{}_{}", type_name, trait_name)
+                    }
+                    (Err(e), _) | (_, Err(e)) => {
+                        errors.add_error(e);
+                        continue;
+                    }
+                };
+                if declared.contains(&name) {
+                    errors.add_error(Error::new_spanned(
+                        &item.self_ty,
+                        format!(
+                            "test module name `{}` derived for the implementation \
+                            is already used by an item in the module",
+                            name
+                        ),
+                    ));
+                    continue;
+                }
+                if !names.insert(name.clone()) {
+                    errors.add_error(Error::new_spanned(
+                        &item.self_ty,
+                        format!(
+                            "duplicate test module name `{}` derived for the implementation",
+                            name
+                        ),
+                    ));
+                    continue;
+                }
+                let mod_item = trait_test_mod(suite, &name, &item.self_ty, trait_path);
+                generated.push((pos + 1, mod_item));
+            }
+            Item::Mod(item) => {
+                let is_inst = item
+                    .attrs
+                    .iter()
+                    .any(|attr| attr.path().is_ident("instantiate_tests"));
+                if let (false, Some((_, content))) = (is_inst, &mut item.content) {
+                    instantiate_trait_suites(suites, content, errors);
+                }
+            }
+            _ => {}
+        }
+    }
+    for (pos, item) in generated.into_iter().rev() {
+        items.insert(pos, item);
+    }
+}
+
+fn trait_test_mod(suite: &TraitSuite, name: &Ident, self_ty: &Type, trait_path: &Path) -> Item {
+    let test_fns = suite.test_methods.iter().map(|method| {
+        let test_attrs = &method.test_attrs;
+        let asyncness = method.asyncness;
+        let unsafety = method.unsafety;
+        let ident = &method.ident;
+        let mut output = method.output.clone();
+        SelfTypeSubst {
+            self_ty,
+            trait_path,
+        }
+        .visit_return_type_mut(&mut output);
+        let call = wrap_async(
+            asyncness,
+            parse_quote! {
+                <#self_ty as #trait_path>::#ident()
+            },
+        );
+        quote! {
+            #(#test_attrs)*
+            #asyncness #unsafety fn #ident() #output {
+                #call
+            }
+        }
+    });
+    parse_quote! {
+        mod #name {
+            #[allow(unused_imports)]
+            use super::*;
+
+            #(#test_fns)*
+        }
+    }
+}
+
+// Replaces `Self` in the signature of a test method with the implementing
+// type, qualified with the trait where an associated item is referred to
+struct SelfTypeSubst<'a> {
+    self_ty: &'a Type,
+    trait_path: &'a Path,
+}
+
+impl VisitMut for SelfTypeSubst<'_> {
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        visit_mut::visit_type_mut(self, ty);
+        let path = match ty {
+            Type::Path(TypePath { qself: None, path }) if path.segments[0].ident == "Self" => path,
+            _ => return,
+        };
+        if path.segments.len() == 1 {
+            *ty = self.self_ty.clone();
+            return;
+        }
+        let self_ty = self.self_ty;
+        let trait_path = self.trait_path;
+        let rest = path.segments.iter().skip(1);
+        *ty = parse_quote! { <#self_ty as #trait_path>::#(#rest)::* };
+    }
+}
+
+// Types in the signature of an instantiated test function, with
+// the generic parameters substituted by the instantiation arguments
+struct InstSignature {
+    fn_args: Vec<FnArg>,
+    output: ReturnType,
+    field_tys: Vec<Type>,
+    ret_ty: Type,
+    // Whether any of the types refer to the instantiation argument aliases
+    uses_inst_args: bool,
+}
+
+impl InstSignature {
+    fn new(test: &TestFn, resolved: &ResolvedArgs) -> Self {
+        let input_sig = &test.sig.input;
+        let mut fn_args = input_sig
+            .args
+            .iter()
+            .map(|arg| arg.to_fn_arg())
+            .collect::<Vec<_>>();
+        let mut output = test.output.clone();
+        let mut field_tys = input_sig
+            .args
+            .iter()
+            .map(|arg| (*arg.field_ty).clone())
+            .collect::<Vec<_>>();
+        let mut ret_ty = (*test.sig.output.ty).clone();
+        let generic_params = &test.sig.generic_params;
+        let uses_inst_args = generic_params.iter().any(|param| param.in_signature);
+        if uses_inst_args {
+            let replacements = resolved
+                .param_bindings
+                .iter()
+                .map(|&i| inst_arg_alias_path(i))
+                .collect();
+            let mut subst = GenericParamSubst::new(generic_params, replacements);
+            for arg in &mut fn_args {
+                subst.visit_fn_arg_mut(arg);
+            }
+            subst.visit_return_type_mut(&mut output);
+            for ty in &mut field_tys {
+                subst.visit_type_mut(ty);
+            }
+            subst.visit_type_mut(&mut ret_ty);
+        }
+        InstSignature {
+            fn_args,
+            output,
+            field_tys,
+            ret_ty,
+            uses_inst_args,
+        }
+    }
+}
+
+// An instantiated test function, one of several if the test has cases
+struct TestInstance {
+    name: Ident,
+    lifetime_params: Punctuated<Lifetime, Token![,]>,
+    where_clause: Option<WhereClause>,
+    fn_args: Vec<FnArg>,
+    args_field_init: Vec<FieldValue>,
+}
+
+impl TestInstance {
+    fn list(test: &TestFn, inst_sig: &InstSignature) -> Vec<Self> {
+        let input_sig = &test.sig.input;
+        if test.cases.is_empty() {
+            return vec![TestInstance {
+                name: test.ident.clone(),
+                lifetime_params: test.sig.lifetime_params.clone(),
+                where_clause: test.sig.lifetime_where_clause(&test.sig.lifetimes()),
+                fn_args: inst_sig.fn_args.clone(),
+                args_field_init: input_sig
+                    .args
+                    .iter()
+                    .map(|arg| {
+                        let ident = &arg.ident;
+                        parse_quote! { #ident }
+                    })
+                    .collect(),
+            }];
+        }
+        // The case values take the place of the arguments, so only
+        // the lifetimes in the output remain in the signature
+        let lifetimes = &test.sig.output.item.lifetimes;
+        let lifetime_params = test
+            .sig
+            .lifetime_params
+            .iter()
+            .filter(|lifetime| lifetimes.contains(lifetime))
+            .cloned()
+            .collect::<Punctuated<_, Token![,]>>();
+        test.cases
+            .iter()
+            .zip(test.instance_names())
+            .map(|(case, name)| TestInstance {
+                name,
+                lifetime_params: lifetime_params.clone(),
+                where_clause: test.sig.lifetime_where_clause(lifetimes),
+                fn_args: Vec::new(),
+                args_field_init: input_sig
+                    .args
+                    .iter()
+                    .zip(&case.values)
+                    .map(|(arg, value)| {
+                        let ident = &arg.ident;
+                        parse_quote! { #ident: #value }
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+// Checks that the fixture expression is given if and only if
+// the selected tests have fixture arguments
+fn check_fixture(inst_args: &InstArguments, opts: &InstOpts, tests: &[&TestFn]) -> syn::Result<()> {
+    let mut fixture_tests = tests
+        .iter()
+        .filter(|test| !test.sig.input.fixture_positions.is_empty());
+    match (opts.fixture(), fixture_tests.next()) {
+        (Some(_), Some(_)) | (None, None) => Ok(()),
+        (Some(fixture), None) => Err(Error::new_spanned(
+            fixture,
+            "fixture is given, but no instantiated test has a `#[fixture]` argument",
+        )),
+        (None, Some(test)) => Err(Error::new_spanned(
+            inst_args,
+            format!(
+                "test function `{}` has a `#[fixture]` argument, \
+                but no `fixture` is given for the instantiation",
+                test.ident
+            ),
+        )),
+    }
+}
+
+fn inst_arg_alias(index: usize) -> Ident {
+    format_ident!("_{}", index)
+}
+
+fn inst_arg_alias_path(index: usize) -> Path {
+    let alias = inst_arg_alias(index);
+    parse_quote! { _generic_tests_inst_args::#alias }
+}
+
+fn shim_mod(
+    test: &TestFn,
+    inst_args: &Punctuated<GenericArgument, Token![,]>,
+    inst_sig: &InstSignature,
+    root_path: &Path,
+    scope_path: &Path,
+    fixture: Option<&Expr>,
+    hook_fns: &[Item],
+) -> Item {
+    let mod_call_sig = call_sig_mod(test, inst_sig, root_path);
     let name = &test.ident;
     let input_sig = &test.sig.input;
-    let fn_args = input_sig
-        .args
-        .iter()
-        .map(|arg| -> Expr {
-            let ident = &arg.ident;
-            parse_quote! { _args.#ident }
+    let mut args = input_sig.args.iter();
+    let fn_args = (0..input_sig.arg_count())
+        .map(|pos| -> Expr {
+            if input_sig.fixture_positions.contains(&pos) {
+                let fixture = fixture.expect("fixture should be checked for the test");
+                parse_quote! { #fixture }
+            } else {
+                let ident = &args.next().unwrap().ident;
+                parse_quote! { _args.#ident }
+            }
         })
         .collect::<Punctuated<_, Token![,]>>();
     let args_path = input_sig.item.path_segment("Args");
@@ -51,7 +446,9 @@ fn shim_mod(test: &TestFn, inst_args: &InstArguments, root_path: &Path) -> Item
     let ret_path = return_sig.item.path_segment("Ret");
     // The order of lifetime parameters is not important, as the call
     // site has them inferred.
-    let lifetimes = input_sig.item.lifetimes.union(&return_sig.item.lifetimes);
+    let lifetimes = test.sig.lifetimes();
+    let where_clause = test.sig.lifetime_where_clause(&lifetimes);
+    let lifetimes = lifetimes.iter();
     let asyncness = test.asyncness;
     let unsafety = test.unsafety;
     let call = wrap_async(
@@ -65,31 +462,42 @@ fn shim_mod(test: &TestFn, inst_args: &InstArguments, root_path: &Path) -> Item
             #mod_call_sig
 
             #[allow(unused_imports)]
-            use super::super::*;
+            use super::#scope_path::*;
 
             pub(super) #asyncness #unsafety fn shim<#(#lifetimes),*>(
                 _args: _generic_tests_call_sig::#args_path,
-            ) -> _generic_tests_call_sig::#ret_path {
+            ) -> _generic_tests_call_sig::#ret_path
+            #where_clause
+            {
                 #call
             }
+
+            #(#hook_fns)*
         }
     }
 }
 
-fn call_sig_mod(test: &TestFn, root_path: &Path) -> Item {
+fn call_sig_mod(test: &TestFn, inst_sig: &InstSignature, root_path: &Path) -> Item {
     let input_sig = &test.sig.input;
     let arg_generics = input_sig.item.lifetime_generics();
+    let arg_where_clause = test.sig.lifetime_where_clause(&input_sig.item.lifetimes);
     let field_ident = input_sig.args.iter().map(|arg| &arg.ident);
-    let field_ty = input_sig.args.iter().map(|arg| &*arg.field_ty);
+    let field_ty = &inst_sig.field_tys;
     let return_sig = &test.sig.output;
     let ret_generics = return_sig.item.lifetime_generics();
-    let ret_ty = &*return_sig.ty;
+    let ret_ty = &inst_sig.ret_ty;
+    let use_inst_args = if inst_sig.uses_inst_args {
+        Some(quote! { use super::super::_generic_tests_inst_args; })
+    } else {
+        None
+    };
     parse_quote! {
         pub(super) mod _generic_tests_call_sig {
             #[allow(unused_imports)]
             use super::super::#root_path::*;
+            #use_inst_args
 
-            pub(in super::super) struct Args #arg_generics {
+            pub(in super::super) struct Args #arg_generics #arg_where_clause {
                 #(pub #field_ident: #field_ty),*
             }
 
@@ -98,6 +506,18 @@ fn call_sig_mod(test: &TestFn, root_path: &Path) -> Item {
     }
 }
 
+fn inst_arg_alias_item(param: &TestGenericParam, index: usize, value: &GenericArgument) -> Item {
+    let alias = inst_arg_alias(index);
+    match &param.kind {
+        TestGenericParamKind::Type { .. } => parse_quote! {
+            pub(super) type #alias = #value;
+        },
+        TestGenericParamKind::Const { ty } => parse_quote! {
+            pub(super) const #alias: #ty = #value;
+        },
+    }
+}
+
 fn wrap_async(asyncness: Option<Token![async]>, expr: Expr) -> Expr {
     if asyncness.is_none() {
         expr
@@ -108,86 +528,705 @@ fn wrap_async(asyncness: Option<Token![async]>, expr: Expr) -> Expr {
 
 struct Instantiator {
     tests: Tests,
+    // Name through which the suite module is imported into the module
+    // at depth 0, if it is not that module itself
+    suite_alias: Option<Ident>,
     depth: u32,
+    matrix_limit: usize,
+    // The enclosing partial instantiation, if any
+    partial: Option<PartialInst>,
+    // Number of instantiation modules visited
+    inst_count: usize,
     errors: ErrorRecord,
 }
 
+// A partial instantiation enclosing the module being processed
+struct PartialInst {
+    args: InstArguments,
+    // Depth of the partial instantiation module
+    depth: u32,
+    // Arguments aliased in the partial instantiation module by their
+    // indices, with the generic parameters they are bound to
+    aliases: BTreeMap<usize, TestGenericParam>,
+}
+
+impl PartialInst {
+    // The value of the argument at `index` of `args` completing this
+    // partial instantiation, as written in a child of the module
+    // at `depth`. Arguments given by the partial instantiation are
+    // referred to by their aliases.
+    fn arg_value(&self, args: &InstArguments, index: usize, depth: u32) -> GenericArgument {
+        let param = match self.aliases.get(&index) {
+            Some(param) => param,
+            None => return args.value(index).clone(),
+        };
+        let mut path = super_path(1 + depth - self.depth);
+        path.segments
+            .push(parse_quote! { _generic_tests_partial_args });
+        path.segments.push(inst_arg_alias(index).into());
+        match param.kind {
+            TestGenericParamKind::Type { .. } => parse_quote! { #path },
+            TestGenericParamKind::Const { .. } => parse_quote! { { #path } },
+        }
+    }
+}
+
 impl Instantiator {
-    fn instantiate_tests(&self, inst_args: InstArguments, content: &mut Vec<Item>) {
+    fn new(opts: &MacroOpts, tests: Tests, suite_alias: Option<Ident>) -> Self {
+        Instantiator {
+            tests,
+            suite_alias,
+            depth: 1,
+            matrix_limit: opts.matrix_limit,
+            partial: None,
+            inst_count: 0,
+            errors: Default::default(),
+        }
+    }
+
+    // Populates `content` with the test functions. The instantiation
+    // arguments are resolved in the scope `scope_depth` levels above
+    // the module of `content`.
+    fn instantiate_tests(
+        &mut self,
+        inst_args: &InstArguments,
+        opts: &InstOpts,
+        scope_depth: u32,
+        content: &mut Vec<Item>,
+    ) {
+        debug_assert!(content.is_empty());
+
+        if inst_args.is_partial() {
+            self.errors.add_error(Error::new_spanned(
+                inst_args,
+                "placeholder arguments must be filled by instantiations in nested modules",
+            ));
+            return;
+        }
+
+        let tests = self
+            .tests
+            .test_fns
+            .iter()
+            .filter(|test| opts.selects(test))
+            .collect::<Vec<_>>();
+
+        if let Err(e) = inst_args.check_bindings_used(&tests) {
+            self.errors.add_error(e);
+        }
+        if let Err(e) = check_fixture(inst_args, opts, &tests) {
+            self.errors.add_error(e);
+            return;
+        }
+
+        let mut module_inst = TestModuleInstantiator {
+            inst_args,
+            opts,
+            tests: &tests,
+            suite: &self.tests,
+            partial: self.partial.as_ref(),
+            suite_alias: self.suite_alias.as_ref(),
+            depth: self.depth,
+            scope_depth,
+            errors: &mut self.errors,
+        };
+        module_inst.instantiate(&[], content);
+    }
+
+    fn instantiate_spec(&mut self, spec: &InstSpec, opts: &InstOpts, content: &mut Vec<Item>) {
+        if let Err(e) = opts.check_test_names(&self.tests.test_fns) {
+            self.errors.add_error(e);
+            return;
+        }
+        match spec {
+            InstSpec::Args(args) => match self.complete_args(args) {
+                Ok(args) => self.instantiate_tests(&args, opts, 1, content),
+                Err(e) => self.errors.add_error(e),
+            },
+            InstSpec::Matrix(matrix) => self.instantiate_matrix(matrix, opts, content),
+        }
+    }
+
+    // Fills the placeholders of the enclosing partial instantiation, if any
+    fn complete_args(&self, args: &InstArguments) -> syn::Result<InstArguments> {
+        match &self.partial {
+            Some(outer) => args.complete(&outer.args),
+            None => Ok(args.clone()),
+        }
+    }
+
+    // Processes instantiations in the submodules of a module with partial
+    // instantiation arguments, which are combined with the arguments of
+    // the nested instantiations.
+    fn instantiate_partial(&mut self, item: &mut ItemMod, args: InstArguments, opts: &InstOpts) {
+        if opts.has_test_options() {
+            self.errors.add_error(Error::new_spanned(
+                &item.ident,
+                "only `cfg()` can be given for a partial instantiation",
+            ));
+        }
+        let (_, content) = item.content.as_mut().unwrap();
+        let aliases = self.partial_arg_aliases(&args);
+        // The given arguments are resolved in the scope enclosing
+        // the module, and the nested instantiations refer to them
+        // through the aliases
+        if !aliases.is_empty() {
+            let alias_items = aliases.iter().map(|(&i, param)| {
+                let value = match &self.partial {
+                    Some(outer) => outer.arg_value(&args, i, self.depth),
+                    None => args.value(i).clone(),
+                };
+                inst_arg_alias_item(param, i, &value)
+            });
+            content.push(parse_quote! {
+                #[allow(non_camel_case_types, non_upper_case_globals)]
+                mod _generic_tests_partial_args {
+                    #[allow(unused_imports)]
+                    use super::super::*;
+
+                    #(#alias_items)*
+                }
+            });
+        }
+        let partial = PartialInst {
+            args,
+            depth: self.depth,
+            aliases,
+        };
+        let outer_partial = self.partial.replace(partial);
+        let inst_count = self.inst_count;
+        self.depth += 1;
+        for nested in content.iter_mut() {
+            self.visit_item_mut(nested);
+        }
+        self.depth -= 1;
+        self.partial = outer_partial;
+        if self.inst_count == inst_count {
+            self.errors.add_error(Error::new_spanned(
+                &item.ident,
+                "partial instantiation must be completed by instantiations in nested modules",
+            ));
+        }
+    }
+
+    // Finds the generic parameters bound to the arguments given in
+    // a partial instantiation, to declare aliases of the arguments
+    // with the kind of the parameters. An argument bound to parameters
+    // of different kinds or const types cannot be aliased and is
+    // left as written.
+    fn partial_arg_aliases(&self, args: &InstArguments) -> BTreeMap<usize, TestGenericParam> {
+        let mut aliases = BTreeMap::<usize, TestGenericParam>::new();
+        let mut mismatched = HashSet::new();
+        let tests = &self.tests;
+        let fns = tests
+            .test_fns
+            .iter()
+            .chain(&tests.setup_fns)
+            .chain(&tests.teardown_fns);
+        for test in fns {
+            // Mismatched arguments are reported by the nested instantiations
+            let resolved = match args.resolve_for(test) {
+                Ok(resolved) => resolved,
+                Err(_) => continue,
+            };
+            for (param, &i) in test.sig.generic_params.iter().zip(&resolved.param_bindings) {
+                if !args.is_given(i) {
+                    continue;
+                }
+                match aliases.get(&i) {
+                    None => {
+                        aliases.insert(i, param.clone());
+                    }
+                    Some(alias) if alias.kind.matches(&param.kind) => {}
+                    Some(_) => {
+                        mismatched.insert(i);
+                    }
+                }
+            }
+        }
+        aliases.retain(|i, _| !mismatched.contains(i));
+        aliases
+    }
+
+    fn instantiate_matrix(
+        &mut self,
+        matrix: &InstMatrix,
+        opts: &InstOpts,
+        content: &mut Vec<Item>,
+    ) {
         debug_assert!(content.is_empty());
 
-        let root_path = self.root_path();
+        match matrix.combination_count() {
+            Some(n) if n <= self.matrix_limit => {}
+            count => {
+                let count = count.map_or_else(|| "too many".into(), |n| n.to_string());
+                self.errors.add_error(Error::new_spanned(
+                    matrix,
+                    format!(
+                        "matrix instantiation produces {} combinations, \
+                        exceeding the limit of {}; \
+                        the limit can be raised with `matrix_limit` in the macro attribute",
+                        count, self.matrix_limit,
+                    ),
+                ));
+                return;
+            }
+        }
+
+        let mut names = HashSet::new();
+        self.depth += 1;
+        for inst_args in matrix.combinations() {
+            let name = match inst_args.mod_name() {
+                Ok(name) => name,
+                Err(e) => {
+                    self.errors.add_error(e);
+                    continue;
+                }
+            };
+            if !names.insert(name.clone()) {
+                self.errors.add_error(Error::new_spanned(
+                    &inst_args,
+                    format!(
+                        "duplicate module name `{}` derived for matrix combination",
+                        name
+                    ),
+                ));
+                continue;
+            }
+            let inst_args = match self.complete_args(&inst_args) {
+                Ok(args) => args,
+                Err(e) => {
+                    self.errors.add_error(e);
+                    continue;
+                }
+            };
+            let mut items = Vec::new();
+            self.instantiate_tests(&inst_args, opts, 2, &mut items);
+            content.push(parse_quote! {
+                mod #name {
+                    #(#items)*
+                }
+            });
+        }
+        self.depth -= 1;
+    }
+
+    // Reports items in the instantiation module that have the same names
+    // as the generated items
+    fn check_name_clashes(&mut self, items: &[Item], generated: &[Item]) {
+        let generated_names = generated
+            .iter()
+            .flat_map(declared_names)
+            .collect::<Vec<_>>();
+        for (ident, ns) in items.iter().flat_map(declared_names) {
+            if generated_names
+                .iter()
+                .any(|(name, gen_ns)| *name == ident && ns.overlaps(*gen_ns))
+            {
+                self.errors.add_error(Error::new_spanned(
+                    &ident,
+                    format!(
+                        "`{}` clashes with an item of the same name \
+                        generated by the instantiation",
+                        ident
+                    ),
+                ));
+            }
+        }
+    }
+}
+
+// Generates the instantiated tests of a module in the suite tree
+struct TestModuleInstantiator<'a> {
+    inst_args: &'a InstArguments,
+    // The partial instantiation completed by `inst_args`, if any
+    partial: Option<&'a PartialInst>,
+    opts: &'a InstOpts,
+    // The selected tests in all modules of the suite
+    tests: &'a [&'a TestFn],
+    suite: &'a Tests,
+    suite_alias: Option<&'a Ident>,
+    // Depth of the instantiation module below the root module
+    depth: u32,
+    scope_depth: u32,
+    errors: &'a mut ErrorRecord,
+}
+
+impl TestModuleInstantiator<'_> {
+    // The instantiation arguments as written in the items generated for
+    // the suite module at `level`, where the arguments given by
+    // the partial instantiation are referred to by their aliases
+    fn level_args(&self, level: u32) -> InstArguments {
+        let mut args = self.inst_args.clone();
+        if let Some(partial) = self.partial {
+            for &i in partial.aliases.keys() {
+                let value = partial.arg_value(&args, i, self.depth + level);
+                args.set_value(i, value);
+            }
+        }
+        args
+    }
+
+    // Populates `content` with the tests of the suite module at
+    // `module_path`, replicating its submodules that have tests selected
+    fn instantiate(&mut self, module_path: &[Ident], content: &mut Vec<Item>) {
+        let level = module_path.len() as u32;
+        let mut root_path = super_path(self.depth + level);
+        root_path.segments.extend(
+            self.suite_alias
+                .into_iter()
+                .chain(module_path)
+                .cloned()
+                .map(PathSegment::from),
+        );
+        let scope_path = super_path(self.scope_depth + level);
+        let inst_args = &self.level_args(level);
+        // The fixture expression is evaluated in the scope of the
+        // instantiation module, which is imported into the block
+        // from within the shim module
+        let fixture = self.opts.fixture().map(|fixture| -> Expr {
+            let inst_mod_path = super_path(self.scope_depth + level);
+            parse_quote! {
+                {
+                    #[allow(unused_imports)]
+                    use #inst_mod_path::*;
+                    #fixture
+                }
+            }
+        });
 
         content.push(parse_quote! {
             #[allow(unused_imports)]
             use #root_path::*;
         });
 
-        for test in &self.tests.test_fns {
-            let test_attrs = &test.test_attrs;
-            let name = &test.ident;
-            let lifetime_params = &test.sig.lifetime_params;
-            let fn_args = test.sig.input.args.iter().map(|arg| arg.to_fn_arg());
-            let output = &test.output;
-            let mod_shim = shim_mod(test, &inst_args, &root_path);
-            let args_field_init = test.sig.input.args.iter().map(|arg| &arg.ident);
+        let mut inst_arg_aliases = BTreeMap::new();
+
+        let tests = self.tests;
+        for test in tests.iter().filter(|test| test.module_path == module_path) {
+            let resolved = match inst_args.resolve_for(test) {
+                Ok(resolved) => resolved,
+                Err(e) => {
+                    self.errors.add_error(e);
+                    continue;
+                }
+            };
+            let inst_sig = InstSignature::new(test, &resolved);
+            let generic_params = &test.sig.generic_params;
+            for (param, &i) in generic_params.iter().zip(&resolved.param_bindings) {
+                if param.in_signature {
+                    inst_arg_aliases
+                        .entry(i)
+                        .or_insert_with(|| inst_arg_alias_item(param, i, inst_args.value(i)));
+                }
+            }
+            let test_attrs = self.opts.test_attrs(test);
+            let output = &inst_sig.output;
+            let (hook_fns, call) = match self.hook_calls(inst_args, test) {
+                Ok(hooks) => hooks,
+                Err(e) => {
+                    self.errors.add_error(e);
+                    continue;
+                }
+            };
+            let mod_shim = shim_mod(
+                test,
+                &resolved.args,
+                &inst_sig,
+                &root_path,
+                &scope_path,
+                fixture.as_ref(),
+                &hook_fns,
+            );
             let asyncness = test.asyncness;
             let unsafety = test.unsafety;
-            let call = wrap_async(
-                asyncness,
-                parse_quote! {
-                    shim::shim(args)
-                },
-            );
+            for test_inst in TestInstance::list(test, &inst_sig) {
+                let name = &test_inst.name;
+                let lifetime_params = &test_inst.lifetime_params;
+                let where_clause = &test_inst.where_clause;
+                let fn_args = &test_inst.fn_args;
+                let args_field_init = &test_inst.args_field_init;
+                content.push(parse_quote! {
+                    #(#test_attrs)*
+                    #asyncness #unsafety fn #name<#lifetime_params>(#(#fn_args),*) #output
+                    #where_clause
+                    {
+                        #mod_shim
+
+                        let args = shim::_generic_tests_call_sig::Args { #(#args_field_init),* };
+                        #(#call)*
+                    }
+                });
+            }
+        }
+
+        if !inst_arg_aliases.is_empty() {
+            let aliases = inst_arg_aliases.into_values();
             content.push(parse_quote! {
-                #(#test_attrs)*
-                #asyncness #unsafety fn #name<#lifetime_params>(#(#fn_args),*) #output {
-                    #mod_shim
+                #[allow(non_camel_case_types, non_upper_case_globals)]
+                mod _generic_tests_inst_args {
+                    #[allow(unused_imports)]
+                    use super::#scope_path::*;
 
-                    let args = shim::_generic_tests_call_sig::Args { #(#args_field_init),* };
-                    #call
+                    #(#aliases)*
                 }
             });
         }
+
+        let submodules = &self.suite.submodules;
+        for submodule in submodules.iter().filter(|submodule| {
+            submodule.path.len() == module_path.len() + 1
+                && submodule.path.starts_with(module_path)
+                && tests
+                    .iter()
+                    .any(|test| test.module_path.starts_with(&submodule.path))
+        }) {
+            let mut items = Vec::new();
+            self.instantiate(&submodule.path, &mut items);
+            let attrs = &submodule.attrs;
+            let name = submodule.path.last().unwrap();
+            content.push(parse_quote! {
+                #(#attrs)*
+                mod #name {
+                    #(#items)*
+                }
+            });
+        }
+    }
+
+    // Generates the shim functions calling the setup and teardown functions
+    // that apply to the test, and the statements of the test body calling
+    // the test through the shim. The teardown functions are called even if
+    // the test panics.
+    fn hook_calls(
+        &self,
+        inst_args: &InstArguments,
+        test: &TestFn,
+    ) -> syn::Result<(Vec<Item>, Vec<Stmt>)> {
+        let setup_fn = self.hook_fn(inst_args, HookKind::Setup, test)?;
+        let teardown_fn = self.hook_fn(inst_args, HookKind::Teardown, test)?;
+        let asyncness = test.asyncness;
+        let mut stmts = Vec::<Stmt>::new();
+        let mut hook_fns = Vec::new();
+        if let Some((item, hook_asyncness)) = setup_fn {
+            let call = wrap_async(hook_asyncness, parse_quote! { shim::setup() });
+            stmts.push(parse_quote! { #call; });
+            hook_fns.push(item);
+        }
+        let (item, hook_asyncness) = match teardown_fn {
+            Some(teardown_fn) => teardown_fn,
+            None => {
+                let call = wrap_async(asyncness, parse_quote! { shim::shim(args) });
+                stmts.push(Stmt::Expr(call, None));
+                return Ok((hook_fns, stmts));
+            }
+        };
+        hook_fns.push(item);
+        if asyncness.is_none() {
+            stmts.push(parse_quote! {
+                let result = ::std::panic::catch_unwind(
+                    ::std::panic::AssertUnwindSafe(move || shim::shim(args)),
+                );
+            });
+        } else {
+            stmts.push(parse_quote! {
+                let mut future = ::std::pin::pin!(shim::shim(args));
+            });
+            stmts.push(parse_quote! {
+                let result = ::std::future::poll_fn(|cx| {
+                    match ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| {
+                        ::std::future::Future::poll(future.as_mut(), cx)
+                    })) {
+                        Ok(::std::task::Poll::Ready(output)) => ::std::task::Poll::Ready(Ok(output)),
+                        Ok(::std::task::Poll::Pending) => ::std::task::Poll::Pending,
+                        Err(payload) => ::std::task::Poll::Ready(Err(payload)),
+                    }
+                })
+                .await;
+            });
+        }
+        let call = wrap_async(hook_asyncness, parse_quote! { shim::teardown() });
+        stmts.push(parse_quote! { #call; });
+        stmts.push(Stmt::Expr(
+            parse_quote! {
+                match result {
+                    Ok(output) => output,
+                    Err(payload) => ::std::panic::resume_unwind(payload),
+                }
+            },
+            None,
+        ));
+        Ok((hook_fns, stmts))
+    }
+
+    // Generates a shim function calling the setup or teardown functions
+    // that apply to the test, outermost first for setup and innermost
+    // first for teardown. Returns the function and its asyncness.
+    fn hook_fn(
+        &self,
+        inst_args: &InstArguments,
+        kind: HookKind,
+        test: &TestFn,
+    ) -> syn::Result<Option<(Item, Option<Token![async]>)>> {
+        let mut hooks = self.suite.hooks_for(kind, &test.module_path);
+        if hooks.is_empty() {
+            return Ok(None);
+        }
+        if kind == HookKind::Teardown {
+            hooks.reverse();
+        }
+        let level = test.module_path.len() as u32;
+        let mut asyncness = None;
+        let mut calls = Vec::<Expr>::with_capacity(hooks.len());
+        for hook in hooks {
+            let resolved = inst_args.resolve_for(hook)?;
+            let args = &resolved.args;
+            let mut hook_path = super_path(self.depth + level);
+            hook_path.segments.extend(
+                self.suite_alias
+                    .into_iter()
+                    .chain(&hook.module_path)
+                    .cloned()
+                    .map(PathSegment::from),
+            );
+            let name = &hook.ident;
+            calls.push(wrap_async(
+                hook.asyncness,
+                parse_quote! { super::#hook_path::#name::<#args>() },
+            ));
+            asyncness = asyncness.or(hook.asyncness);
+        }
+        let name = match kind {
+            HookKind::Setup => format_ident!("setup"),
+            HookKind::Teardown => format_ident!("teardown"),
+        };
+        let item = parse_quote! {
+            pub(super) #asyncness fn #name() {
+                #(#calls;)*
+            }
+        };
+        Ok(Some((item, asyncness)))
     }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Namespace {
+    Type,
+    Value,
+    // Names imported with `use` can be in either namespace
+    Any,
+}
+
+impl Namespace {
+    fn overlaps(self, other: Namespace) -> bool {
+        self == other || self == Namespace::Any || other == Namespace::Any
+    }
+}
 
-    fn root_path(&self) -> Path {
-        let mut segments = Punctuated::new();
-        for _ in 0..self.depth {
-            segments.push(parse_quote! { super });
+// Lists the names that an item declares in the module scope
+fn declared_names(item: &Item) -> Vec<(Ident, Namespace)> {
+    use Namespace::*;
+
+    match item {
+        Item::Fn(item) => vec![(item.sig.ident.clone(), Value)],
+        Item::Const(item) => vec![(item.ident.clone(), Value)],
+        Item::Static(item) => vec![(item.ident.clone(), Value)],
+        Item::Struct(item) => match item.fields {
+            Fields::Named(_) => vec![(item.ident.clone(), Type)],
+            Fields::Unnamed(_) | Fields::Unit => {
+                vec![(item.ident.clone(), Type), (item.ident.clone(), Value)]
+            }
+        },
+        Item::Enum(item) => vec![(item.ident.clone(), Type)],
+        Item::Union(item) => vec![(item.ident.clone(), Type)],
+        Item::Trait(item) => vec![(item.ident.clone(), Type)],
+        Item::TraitAlias(item) => vec![(item.ident.clone(), Type)],
+        Item::Type(item) => vec![(item.ident.clone(), Type)],
+        Item::Mod(item) => vec![(item.ident.clone(), Type)],
+        Item::ExternCrate(item) => {
+            let ident = item
+                .rename
+                .as_ref()
+                .map_or(&item.ident, |(_, rename)| rename);
+            vec![(ident.clone(), Type)]
+        }
+        Item::Use(item) => {
+            let mut names = Vec::new();
+            collect_use_names(&item.tree, &mut names);
+            names.into_iter().map(|ident| (ident, Any)).collect()
         }
-        Path {
-            leading_colon: None,
-            segments,
+        _ => Vec::new(),
+    }
+}
+
+// Checks if an item in `items` certainly declares `ident` in the type
+// namespace. Imported names are not considered, as they may as well be
+// in the value namespace only.
+fn declares_type_name(items: &[Item], ident: &Ident) -> bool {
+    items
+        .iter()
+        .flat_map(declared_names)
+        .any(|(name, ns)| name == *ident && ns == Namespace::Type)
+}
+
+fn collect_use_names(tree: &UseTree, names: &mut Vec<Ident>) {
+    match tree {
+        UseTree::Path(path) => collect_use_names(&path.tree, names),
+        UseTree::Name(name) if name.ident != "self" => names.push(name.ident.clone()),
+        UseTree::Name(_) => {}
+        UseTree::Rename(rename) => names.push(rename.rename.clone()),
+        UseTree::Glob(_) => {}
+        UseTree::Group(group) => {
+            for tree in &group.items {
+                collect_use_names(tree, names);
+            }
         }
     }
 }
 
+fn super_path(depth: u32) -> Path {
+    let mut segments = Punctuated::new();
+    for _ in 0..depth {
+        segments.push(parse_quote! { super });
+    }
+    Path {
+        leading_colon: None,
+        segments,
+    }
+}
+
 impl VisitMut for Instantiator {
     fn visit_item_mod_mut(&mut self, item: &mut ItemMod) {
         debug_assert_ne!(self.depth, 0);
-        match InstArguments::try_extract(item) {
-            Ok(Some(args)) => {
-                let content = match &mut item.content {
-                    None => {
-                        self.errors.add_error(Error::new_spanned(
-                            item,
-                            "module to instantiate tests into must be inline",
-                        ));
-                        return;
-                    }
-                    Some((_, content)) => {
-                        if !content.is_empty() {
-                            self.errors.add_error(Error::new_spanned(
-                                item,
-                                "module to instantiate tests into must be empty",
-                            ));
+        match InstAttrArgs::try_extract(item) {
+            Ok(Some(InstAttrArgs { spec, opts })) => {
+                self.inst_count += 1;
+                // A module declared without a body is made inline
+                item.semi = None;
+                item.content
+                    .get_or_insert_with(|| (Brace::default(), Vec::new()));
+                let partial_args = match &spec {
+                    InstSpec::Args(args) => match self.complete_args(args) {
+                        Ok(args) if args.is_partial() => Some(args),
+                        Ok(_) => None,
+                        Err(e) => {
+                            self.errors.add_error(e);
                             return;
                         }
-                        content
-                    }
+                    },
+                    InstSpec::Matrix(_) => None,
                 };
-                self.instantiate_tests(args, content);
+                match partial_args {
+                    Some(args) => self.instantiate_partial(item, args, &opts),
+                    None => {
+                        let (_, content) = item.content.as_mut().unwrap();
+                        let mut generated = Vec::new();
+                        self.instantiate_spec(&spec, &opts, &mut generated);
+                        self.check_name_clashes(content, &generated);
+                        content.extend(generated);
+                    }
+                }
+                item.attrs.extend(opts.mod_attrs());
             }
             Ok(None) => {
                 self.depth += 1;
@@ -0,0 +1,486 @@
+use crate::expand;
+use crate::extract::{HookKind, Tests};
+use crate::instantiation::InstAttrArgs;
+use crate::module_file;
+use crate::naming;
+use crate::options::{MacroOpts, ParsedMacroOpts};
+
+use proc_macro2::{Delimiter, Group, Spacing, Span, TokenStream, TokenTree};
+use quote::{format_ident, quote, ToTokens};
+use syn::ext::IdentExt;
+use syn::parse::{Parse, ParseStream};
+use syn::{parse_quote, Token};
+use syn::{Attribute, Error, Ident, Item, ItemMod, Path};
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::iter;
+use std::mem;
+
+const COMPANION_MACRO: &str = "_generic_tests_suite";
+
+// Marks the module containing the declarations of an included suite
+pub const INCLUDED_ATTR: &str = "_generic_tests_included";
+
+// Makes the suite available to the `instantiate` attribute used outside
+// of the suite module. The generic test functions are replayed with
+// empty bodies to a `macro_rules!` companion, which passes them on
+// to the `__instantiate` macro along with the instantiation module.
+pub fn suite_companion(opts: &MacroOpts, ident: &Ident, tests: &Tests) -> Vec<Item> {
+    let companion = format_ident!("{}", COMPANION_MACRO);
+    let matrix_limit = opts.matrix_limit;
+    let mut replay = replay_module(tests, &[]);
+    if opts.export {
+        // Paths in the declarations must resolve in the defining crate
+        replay = dollar_crate_paths(replay);
+    }
+    let rules = quote! {
+        ($($input:tt)*) => {
+            ::generic_tests::__instantiate! {
+                #[generic_tests::define(matrix_limit = #matrix_limit)]
+                mod #ident {
+                    #replay
+                }
+                $($input)*
+            }
+        };
+    };
+    if opts.export {
+        // An exported macro is placed in the crate root, so it needs
+        // a name unique in the crate. It is re-exported in the suite
+        // module under the common name.
+        let exported = exported_macro_name(ident, &rules);
+        vec![
+            parse_quote! {
+                #[doc(hidden)]
+                #[macro_export]
+                macro_rules! #exported { #rules }
+            },
+            parse_quote! {
+                #[doc(hidden)]
+                pub use #exported as #companion;
+            },
+        ]
+    } else {
+        vec![
+            parse_quote! {
+                #[doc(hidden)]
+                #[allow(unused_macros)]
+                macro_rules! #companion { #rules }
+            },
+            parse_quote! {
+                #[doc(hidden)]
+                #[allow(unused_imports)]
+                pub(crate) use #companion;
+            },
+        ]
+    }
+}
+
+// Derives the name of the exported companion macro from the name
+// of the suite and the declarations replayed by the macro
+fn exported_macro_name(ident: &Ident, rules: &TokenStream) -> Ident {
+    let mut hasher = DefaultHasher::new();
+    ident.unraw().to_string().hash(&mut hasher);
+    rules.to_string().hash(&mut hasher);
+    format_ident!(
+        "{}_{}_{:016x}",
+        COMPANION_MACRO,
+        ident.unraw(),
+        hasher.finish()
+    )
+}
+
+// Replaces `crate` at the start of paths with `$crate`, so that
+// the paths refer to the defining crate when the tokens are expanded
+// by an exported macro in another crate.
+fn dollar_crate_paths(tokens: TokenStream) -> TokenStream {
+    let mut output = TokenStream::new();
+    let mut after_path_sep = false;
+    for tt in tokens {
+        match tt {
+            TokenTree::Ident(ident) if ident == "crate" && !after_path_sep => {
+                output.extend(quote! { $ });
+                output.extend(iter::once(TokenTree::Ident(ident)));
+                after_path_sep = false;
+            }
+            TokenTree::Group(group) => {
+                let stream = group.stream();
+                // Leave the visibility restriction `pub(crate)` as it is
+                let is_vis_crate = group.delimiter() == Delimiter::Parenthesis
+                    && matches!(
+                        &stream.clone().into_iter().collect::<Vec<_>>()[..],
+                        [TokenTree::Ident(ident)] if ident == "crate"
+                    );
+                let stream = if is_vis_crate {
+                    stream
+                } else {
+                    dollar_crate_paths(stream)
+                };
+                let mut replaced = Group::new(group.delimiter(), stream);
+                replaced.set_span(group.span());
+                output.extend(iter::once(TokenTree::Group(replaced)));
+                after_path_sep = false;
+            }
+            TokenTree::Punct(punct) => {
+                // The second colon of `::` is preceded by a joint colon
+                after_path_sep =
+                    punct.as_char() == ':' && (after_path_sep || punct.spacing() == Spacing::Joint);
+                output.extend(iter::once(TokenTree::Punct(punct)));
+            }
+            tt => {
+                after_path_sep = false;
+                output.extend(iter::once(tt));
+            }
+        }
+    }
+    output
+}
+
+// Declarations of the tests in the suite module at `module_path`
+fn replay_module(tests: &Tests, module_path: &[Ident]) -> TokenStream {
+    let mut tokens = TokenStream::new();
+    for test in tests
+        .test_fns
+        .iter()
+        .filter(|test| test.module_path == module_path)
+    {
+        // All attributes collected from the function are test attributes
+        let test_attrs = &test.test_attrs;
+        let attr_paths = test_attrs.iter().map(|attr| attr.path());
+        let sig = &test.decl_sig;
+        let cases = test.cases.iter().map(|case| &case.values);
+        tokens.extend(quote! {
+            #[generic_test(attrs(#(#attr_paths),*), copy_attrs() #(, case(#cases))*)]
+            #(#test_attrs)*
+            #sig {}
+        });
+    }
+    for kind in HookKind::ALL {
+        let hook_fns = match kind {
+            HookKind::Setup => &tests.setup_fns,
+            HookKind::Teardown => &tests.teardown_fns,
+        };
+        if let Some(hook) = hook_fns.iter().find(|f| f.module_path == module_path) {
+            let attr = Ident::new(kind.attr_name(), Span::call_site());
+            let sig = &hook.decl_sig;
+            tokens.extend(quote! {
+                #[#attr]
+                #sig {}
+            });
+        }
+    }
+    for submodule in tests.submodules.iter().filter(|submodule| {
+        submodule.path.len() == module_path.len() + 1 && submodule.path.starts_with(module_path)
+    }) {
+        let attrs = &submodule.attrs;
+        let name = submodule.path.last().unwrap();
+        let content = replay_module(tests, &submodule.path);
+        tokens.extend(quote! {
+            #(#attrs)*
+            mod #name {
+                #content
+            }
+        });
+    }
+    tokens
+}
+
+// Arguments of the `instantiate` attribute
+struct ExternalInstArgs {
+    suite_path: Path,
+    inst_args: TokenStream,
+}
+
+impl Parse for ExternalInstArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let suite_path = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let inst_args: TokenStream = input.parse()?;
+        // Check the arguments early to report errors at the attribute
+        syn::parse2::<InstAttrArgs>(inst_args.clone())?;
+        Ok(ExternalInstArgs {
+            suite_path,
+            inst_args,
+        })
+    }
+}
+
+fn suite_alias(item: &ItemMod) -> Ident {
+    format_ident!("_generic_tests_suite_{}", item.ident.unraw())
+}
+
+pub fn expand_attr(args: TokenStream, item: ItemMod) -> TokenStream {
+    match syn::parse2(args) {
+        Ok(args) => instantiate_in(args, item),
+        Err(e) => e.to_compile_error(),
+    }
+}
+
+fn instantiate_in(args: ExternalInstArgs, item: ItemMod) -> TokenStream {
+    let ExternalInstArgs {
+        suite_path,
+        inst_args,
+    } = args;
+    let alias = suite_alias(&item);
+    // The suite may not exist where the module is configured out
+    let cfg_attrs = item
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("cfg"))
+        .collect::<Vec<_>>();
+    let companion = format_ident!("{}", COMPANION_MACRO);
+    quote! {
+        #(#cfg_attrs)*
+        #[allow(unused_imports)]
+        use #suite_path as #alias;
+
+        #(#cfg_attrs)*
+        #suite_path::#companion! {
+            #[instantiate_tests(#inst_args)]
+            #item
+        }
+    }
+}
+
+// Arguments of the `test_with` attribute: the suite path, optionally
+// followed by the instantiation arguments and options
+struct TestWithArgs {
+    suite_path: Path,
+    // Whether the instantiation arguments are given explicitly
+    has_spec: bool,
+    rest: TokenStream,
+}
+
+impl Parse for TestWithArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let suite_path = input.parse()?;
+        if input.is_empty() {
+            return Ok(TestWithArgs {
+                suite_path,
+                has_spec: false,
+                rest: TokenStream::new(),
+            });
+        }
+        input.parse::<Token![,]>()?;
+        let has_spec = input.peek(Token![<])
+            || input
+                .fork()
+                .parse::<Ident>()
+                .is_ok_and(|ident| ident == "matrix");
+        let rest = input.parse()?;
+        Ok(TestWithArgs {
+            suite_path,
+            has_spec,
+            rest,
+        })
+    }
+}
+
+pub fn expand_test_with(args: TokenStream, item: Item) -> TokenStream {
+    match test_with(args, &item) {
+        Ok(tests) => quote! {
+            #item
+            #tests
+        },
+        Err(e) => {
+            let e = e.to_compile_error();
+            quote! {
+                #item
+                #e
+            }
+        }
+    }
+}
+
+// Instantiates the suite in a sibling module of the annotated type,
+// with the type as the argument unless the arguments are given
+fn test_with(args: TokenStream, item: &Item) -> syn::Result<TokenStream> {
+    let TestWithArgs {
+        suite_path,
+        has_spec,
+        rest,
+    } = syn::parse2(args)?;
+    let (ident, generics) = match item {
+        Item::Struct(item) => (&item.ident, &item.generics),
+        Item::Enum(item) => (&item.ident, &item.generics),
+        Item::Union(item) => (&item.ident, &item.generics),
+        Item::Type(item) => (&item.ident, &item.generics),
+        _ => {
+            return Err(Error::new(
+                Span::call_site(),
+                "expected a struct, enum, union, or type alias",
+            ))
+        }
+    };
+    let inst_args = if has_spec {
+        rest
+    } else if !generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            generics,
+            "instantiation arguments must be given for a generic type",
+        ));
+    } else if rest.is_empty() {
+        quote! { <#ident> }
+    } else {
+        quote! { <#ident>, #rest }
+    };
+    let suite_name = &suite_path.segments.last().unwrap().ident;
+    let type_name = naming::derive_mod_name(ident)?;
+    let name = format_ident!("{}_{}", type_name, suite_name.unraw());
+    let args = syn::parse2(quote! { #suite_path, #inst_args })?;
+    let item = parse_quote! {
+        #[cfg(test)]
+        mod #name {}
+    };
+    Ok(instantiate_in(args, item))
+}
+
+// Input of the `__instantiate` macro, produced by the companion macro
+struct ExternalInput {
+    suite: ItemMod,
+    inst: ItemMod,
+}
+
+impl Parse for ExternalInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let suite = input.parse()?;
+        let inst = input.parse()?;
+        Ok(ExternalInput { suite, inst })
+    }
+}
+
+pub fn expand_instantiation(input: TokenStream) -> TokenStream {
+    match instantiate(input) {
+        Ok(tokens) => tokens,
+        Err(e) => e.to_compile_error(),
+    }
+}
+
+// Requests the declarations of the first included suite from its
+// companion macro. The items of the suite module are passed along in
+// the request and expanded once all the included suites are collected.
+pub fn request_includes(opts: &MacroOpts, args: TokenStream, ast: &mut ItemMod) -> syn::Result<()> {
+    if ast.content.is_none() {
+        module_file::load(ast)?;
+    }
+    let mut names = HashSet::new();
+    for path in &opts.includes {
+        let name = included_suite_name(path)?;
+        if !names.insert(name.clone()) {
+            return Err(Error::new_spanned(
+                path,
+                format!("duplicate name `{}` of an included suite", name),
+            ));
+        }
+    }
+    let first = &opts.includes[0];
+    let companion = format_ident!("{}", COMPANION_MACRO);
+    let ident = &ast.ident;
+    let (_, items) = ast.content.as_mut().unwrap();
+    let suite_items = mem::take(items);
+    items.push(parse_quote! {
+        #first::#companion! {
+            #[generic_tests::define(#args)]
+            mod #ident {
+                #(#suite_items)*
+            }
+        }
+    });
+    Ok(())
+}
+
+// The tests of an included suite are instantiated in a submodule
+// named after the last segment of the suite path
+fn included_suite_name(path: &Path) -> syn::Result<&Ident> {
+    let ident = &path.segments.last().unwrap().ident;
+    if ident == "self" || ident == "super" || ident == "crate" {
+        return Err(Error::new_spanned(
+            path,
+            "expected a path to a suite module",
+        ));
+    }
+    Ok(ident)
+}
+
+// Adds the declarations of an included suite to the suite module,
+// then requests the next included suite or expands the module into
+// its items once all included suites are collected.
+fn include_suite(
+    included: ItemMod,
+    mut suite: ItemMod,
+    define_attr: Attribute,
+) -> syn::Result<TokenStream> {
+    let mut opts = ParsedMacroOpts::default();
+    define_attr.parse_nested_meta(|meta| opts.parse(meta))?;
+    let opts = opts.into_effective();
+    let included_attr = format_ident!("{}", INCLUDED_ATTR);
+    let (_, items) = suite.content.as_mut().unwrap();
+    let included_count = items
+        .iter()
+        .filter(|item| match item {
+            Item::Mod(item) => item
+                .attrs
+                .iter()
+                .any(|attr| attr.path().is_ident(INCLUDED_ATTR)),
+            _ => false,
+        })
+        .count();
+    let path = &opts.includes[included_count];
+    let name = included_suite_name(path)?;
+    let (_, included_items) = included.content.unwrap_or_default();
+    items.insert(
+        included_count,
+        parse_quote! {
+            #[#included_attr(#path)]
+            mod #name {
+                #(#included_items)*
+            }
+        },
+    );
+    match opts.includes.get(included_count + 1) {
+        Some(next) => {
+            let companion = format_ident!("{}", COMPANION_MACRO);
+            Ok(quote! {
+                #next::#companion! {
+                    #define_attr
+                    #suite
+                }
+            })
+        }
+        None => {
+            expand::transform(&opts, &mut suite)?;
+            let (_, items) = suite.content.unwrap();
+            Ok(quote! { #(#items)* })
+        }
+    }
+}
+
+fn instantiate(input: TokenStream) -> syn::Result<TokenStream> {
+    let ExternalInput {
+        mut suite,
+        mut inst,
+    } = syn::parse2(input)?;
+    // A suite module including the suite is passed with its `define`
+    // attribute
+    if let Some(pos) = inst.attrs.iter().position(|attr| {
+        attr.path()
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "define")
+    }) {
+        let define_attr = inst.attrs.remove(pos);
+        return include_suite(suite, inst, define_attr);
+    }
+    let mut opts = ParsedMacroOpts::default();
+    match suite.attrs.pop() {
+        Some(attr) => attr.parse_nested_meta(|meta| opts.parse(meta))?,
+        None => return Err(Error::new_spanned(&suite, "expected suite options")),
+    }
+    let opts = opts.into_effective();
+    let (tests, _) = Tests::try_extract(&opts, &mut suite)?;
+    let alias = suite_alias(&inst);
+    expand::instantiate_external(&opts, tests, alias, &mut inst)?;
+    Ok(inst.into_token_stream())
+}
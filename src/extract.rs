@@ -1,28 +1,82 @@
 use crate::error::ErrorRecord;
-use crate::options::{self, MacroOpts, TestFnOpts};
-use crate::signature::TestFnSignature;
+use crate::external;
+use crate::options::{self, MacroOpts, TestCase, TestFnOpts};
+use crate::signature::{self, TestFnSignature};
 
-use proc_macro2::TokenStream;
-use quote::ToTokens;
-use syn::punctuated::Punctuated;
-use syn::Token;
+use quote::format_ident;
+use syn::ext::IdentExt;
+use syn::{parse_quote, Token};
 use syn::{
-    AngleBracketedGenericArguments, AttrStyle, Attribute, Error, GenericArgument, GenericParam,
-    Generics, Ident, Item, ItemFn, ItemMod, ReturnType,
+    Attribute, Error, FnArg, GenericParam, Generics, Ident, Item, ItemFn, ItemMod, ItemTrait, Path,
+    ReturnType, Signature, TraitItem, Visibility,
 };
 
+use std::mem;
+
 #[derive(Default)]
 pub struct Tests {
     pub test_fns: Vec<TestFn>,
+    // Submodules containing generic test functions, in the order
+    // of their declaration, parents before children
+    pub submodules: Vec<TestSubmodule>,
+    // Traits with test methods, instantiated by their implementations
+    pub trait_suites: Vec<TraitSuite>,
+    // Functions marked with `#[generic_setup]`, at most one per module
+    pub setup_fns: Vec<TestFn>,
+    // Functions marked with `#[generic_teardown]`, at most one per module
+    pub teardown_fns: Vec<TestFn>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum HookKind {
+    Setup,
+    Teardown,
+}
+
+impl HookKind {
+    pub const ALL: [HookKind; 2] = [HookKind::Setup, HookKind::Teardown];
+
+    pub fn attr_name(self) -> &'static str {
+        match self {
+            HookKind::Setup => "generic_setup",
+            HookKind::Teardown => "generic_teardown",
+        }
+    }
+}
+
+pub struct TestSubmodule {
+    // Path of the submodule relative to the root module
+    pub path: Vec<Ident>,
+    // Attributes copied to the instantiated submodules
+    pub attrs: Vec<Attribute>,
 }
 
 pub struct TestFn {
+    // Path of the submodule defining the function, empty for the root module
+    pub module_path: Vec<Ident>,
     pub test_attrs: Vec<Attribute>,
     pub asyncness: Option<Token![async]>,
     pub unsafety: Option<Token![unsafe]>,
     pub ident: Ident,
     pub output: ReturnType,
     pub sig: TestFnSignature,
+    // The signature as declared, with any parameters from the macro options
+    pub decl_sig: Signature,
+    // Argument values for which the test is instantiated, if any are given
+    pub cases: Vec<TestCase>,
+}
+
+pub struct TraitSuite {
+    pub ident: Ident,
+    pub test_methods: Vec<TestMethod>,
+}
+
+pub struct TestMethod {
+    pub test_attrs: Vec<Attribute>,
+    pub asyncness: Option<Token![async]>,
+    pub unsafety: Option<Token![unsafe]>,
+    pub ident: Ident,
+    pub output: ReturnType,
 }
 
 impl Tests {
@@ -39,131 +93,585 @@ impl Tests {
         Ok((tests, items))
     }
 
-    fn extract_recording_errors(opts: &MacroOpts, items: &mut [Item]) -> (Self, ErrorRecord) {
+    fn extract_recording_errors(opts: &MacroOpts, items: &mut Vec<Item>) -> (Self, ErrorRecord) {
         let mut errors = ErrorRecord::default();
         let mut tests = Tests::default();
-        let mut mod_wide_generic_arity = None;
+        tests.extract_included(opts, items, &mut errors);
+        tests.extract_from_items(opts, &mut Vec::new(), items, &mut errors);
+        tests.check_async_hooks(&mut errors);
+        tests.check_case_names(&mut errors);
+        (tests, errors)
+    }
+
+    // Collects the tests of the suites given in the `include()` option.
+    // The declarations of each included suite are passed in a marked module,
+    // which is replaced with a module re-exporting the included suite's items
+    // for the instantiated tests to use.
+    fn extract_included(
+        &mut self,
+        opts: &MacroOpts,
+        items: &mut Vec<Item>,
+        errors: &mut ErrorRecord,
+    ) {
+        let replay_opts = MacroOpts::default();
+        let mut aliases = Vec::<Item>::new();
         for item in items.iter_mut() {
-            if let Item::Fn(item) = item {
-                match TestFn::try_extract(opts, item) {
-                    Ok(None) => {}
-                    Ok(Some(test_fn)) => {
-                        let fn_generic_arity = generic_arity(&item.sig.generics);
-                        match mod_wide_generic_arity {
-                            None => {
-                                mod_wide_generic_arity = Some(fn_generic_arity);
-                            }
-                            Some(n) => {
-                                if fn_generic_arity != n {
-                                    errors.add_error(Error::new_spanned(
-                                        &item.sig.generics,
-                                        format!(
-                                            "test function `{}` has {} generic parameters \
-                                            while others in the same module have {}",
-                                            item.sig.ident, fn_generic_arity, n
-                                        ),
-                                    ));
-                                    continue;
-                                }
-                            }
-                        }
-                        tests.test_fns.push(test_fn);
-                    }
-                    Err(e) => {
+            let item_mod = match item {
+                Item::Mod(item_mod) => item_mod,
+                _ => continue,
+            };
+            let pos = match item_mod
+                .attrs
+                .iter()
+                .position(|attr| attr.path().is_ident(external::INCLUDED_ATTR))
+            {
+                Some(pos) => pos,
+                None => continue,
+            };
+            let attr = item_mod.attrs.remove(pos);
+            let path = match attr.parse_args_with(Path::parse_mod_style) {
+                Ok(path) => path,
+                Err(e) => {
+                    errors.add_error(e);
+                    continue;
+                }
+            };
+            let name = item_mod.ident.clone();
+            let mut module_path = vec![name.clone()];
+            self.submodules.push(TestSubmodule {
+                path: module_path.clone(),
+                attrs: Vec::new(),
+            });
+            if let Some((_, content)) = &mut item_mod.content {
+                self.extract_from_items(&replay_opts, &mut module_path, content, errors);
+            }
+            // Glob re-exports work regardless of the visibility of
+            // the included suite module
+            let alias = format_ident!("_generic_tests_include_{}", name.unraw());
+            let vis: Visibility = if opts.export {
+                parse_quote! { pub }
+            } else {
+                parse_quote! { pub(crate) }
+            };
+            aliases.push(parse_quote! {
+                #[allow(unused_imports)]
+                use #path as #alias;
+            });
+            *item = parse_quote! {
+                #vis mod #name {
+                    #[allow(unused_imports)]
+                    #vis use super::#alias::*;
+                }
+            };
+        }
+        items.extend(aliases);
+    }
+
+    // Collects the generic test functions from the items of the module
+    // at `module_path`, recursing into submodules that are not
+    // instantiation targets.
+    fn extract_from_items(
+        &mut self,
+        opts: &MacroOpts,
+        module_path: &mut Vec<Ident>,
+        items: &mut [Item],
+        errors: &mut ErrorRecord,
+    ) {
+        for item in items.iter_mut() {
+            match item {
+                Item::Fn(item) => {
+                    let res = match take_hook_attr(&mut item.attrs) {
+                        Ok(Some(kind)) => self.extract_hook(opts, module_path, kind, item),
+                        Ok(None) => TestFn::try_extract(opts, module_path, item)
+                            .map(|test_fn| self.test_fns.extend(test_fn)),
+                        Err(e) => Err(e),
+                    };
+                    if let Err(e) = res {
                         errors.add_error(e);
-                        continue;
                     }
                 }
+                Item::Mod(item) => self.extract_from_submodule(opts, module_path, item, errors),
+                Item::Trait(item) => match TraitSuite::try_extract(opts, item) {
+                    Ok(None) => {}
+                    Ok(Some(suite)) => self.trait_suites.push(suite),
+                    Err(e) => errors.add_error(e),
+                },
+                _ => {}
             }
         }
-        (tests, errors)
+    }
+
+    fn extract_hook(
+        &mut self,
+        opts: &MacroOpts,
+        module_path: &[Ident],
+        kind: HookKind,
+        item: &mut ItemFn,
+    ) -> syn::Result<()> {
+        let hook_fns = match kind {
+            HookKind::Setup => &mut self.setup_fns,
+            HookKind::Teardown => &mut self.teardown_fns,
+        };
+        if hook_fns.iter().any(|f| f.module_path == module_path) {
+            return Err(Error::new_spanned(
+                &item.sig.ident,
+                format!("duplicate `{}` function in the module", kind.attr_name()),
+            ));
+        }
+        if !item.sig.inputs.is_empty() {
+            return Err(Error::new_spanned(
+                &item.sig.inputs,
+                "setup and teardown functions cannot have parameters",
+            ));
+        }
+        if let Some(unsafety) = &item.sig.unsafety {
+            return Err(Error::new_spanned(
+                unsafety,
+                "setup and teardown functions cannot be unsafe",
+            ));
+        }
+        if let ReturnType::Type(..) = item.sig.output {
+            return Err(Error::new_spanned(
+                &item.sig.output,
+                "setup and teardown functions cannot return a value",
+            ));
+        }
+        if let Some(params) = &opts.params {
+            add_generic_params(&mut item.sig.generics, params)?;
+        }
+        let sig = TestFnSignature::try_build(item)?;
+        make_visible(opts, &mut item.vis);
+        hook_fns.push(TestFn::new(module_path, Vec::new(), item.sig.clone(), sig));
+        Ok(())
+    }
+
+    // Async setup and teardown functions can only be awaited in async tests
+    fn check_async_hooks(&self, errors: &mut ErrorRecord) {
+        for test in self.test_fns.iter().filter(|test| test.asyncness.is_none()) {
+            for kind in HookKind::ALL {
+                if let Some(hook) = self
+                    .hooks_for(kind, &test.module_path)
+                    .into_iter()
+                    .find(|hook| hook.asyncness.is_some())
+                {
+                    errors.add_error(Error::new_spanned(
+                        &test.ident,
+                        format!(
+                            "test function `{}` is not async, \
+                            but async `{}` function `{}` applies to it",
+                            test.ident,
+                            kind.attr_name(),
+                            hook.ident,
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+
+    // The names generated for test cases must not clash with the names
+    // of other tests instantiated in the same module. As the generated
+    // names end with the case number, they can only clash with the names
+    // of tests without cases.
+    fn check_case_names(&self, errors: &mut ErrorRecord) {
+        for test in self.test_fns.iter().filter(|test| test.cases.is_empty()) {
+            if let Some(cased) = self.test_fns.iter().find(|other| {
+                other.module_path == test.module_path
+                    && !other.cases.is_empty()
+                    && other.instance_names().contains(&test.ident)
+            }) {
+                errors.add_error(Error::new_spanned(
+                    &test.ident,
+                    format!(
+                        "test function `{}` clashes with the name generated \
+                        for a case of `{}`",
+                        test.ident, cased.ident,
+                    ),
+                ));
+            }
+        }
+    }
+
+    // The setup or teardown functions applying to tests in the module
+    // at `module_path`, outermost first
+    pub fn hooks_for(&self, kind: HookKind, module_path: &[Ident]) -> Vec<&TestFn> {
+        let hook_fns = match kind {
+            HookKind::Setup => &self.setup_fns,
+            HookKind::Teardown => &self.teardown_fns,
+        };
+        let mut hooks = hook_fns
+            .iter()
+            .filter(|f| module_path.starts_with(&f.module_path))
+            .collect::<Vec<_>>();
+        hooks.sort_by_key(|f| f.module_path.len());
+        hooks
+    }
+
+    fn extract_from_submodule(
+        &mut self,
+        opts: &MacroOpts,
+        module_path: &mut Vec<Ident>,
+        item: &mut ItemMod,
+        errors: &mut ErrorRecord,
+    ) {
+        if item
+            .attrs
+            .iter()
+            .any(|attr| attr.path().is_ident("instantiate_tests"))
+        {
+            return;
+        }
+        let items = match &mut item.content {
+            Some((_, items)) => items,
+            None => return,
+        };
+        module_path.push(item.ident.clone());
+        let test_count = self.test_fns.len();
+        let submodule_pos = self.submodules.len();
+        self.extract_from_items(opts, module_path, items, errors);
+        if self.test_fns.len() != test_count {
+            let attrs = item
+                .attrs
+                .iter()
+                .filter(|attr| options::is_copied_attr(attr, opts, &TestFnOpts::default()))
+                .cloned()
+                .collect();
+            self.submodules.insert(
+                submodule_pos,
+                TestSubmodule {
+                    path: module_path.clone(),
+                    attrs,
+                },
+            );
+            // The instantiated tests need access to the module
+            make_visible(opts, &mut item.vis);
+        }
+        module_path.pop();
     }
 }
 
 impl TestFn {
-    fn try_extract(opts: &MacroOpts, item: &mut ItemFn) -> syn::Result<Option<Self>> {
-        let test_attrs = extract_test_attrs(opts, item)?;
+    // The names of the test functions instantiated from this one:
+    // one for each case if cases are given, otherwise its own name
+    pub fn instance_names(&self) -> Vec<Ident> {
+        if self.cases.is_empty() {
+            return vec![self.ident.clone()];
+        }
+        (1..=self.cases.len())
+            .map(|n| format_ident!("{}_case_{}", self.ident, n))
+            .collect()
+    }
+
+    fn try_extract(
+        opts: &MacroOpts,
+        module_path: &[Ident],
+        item: &mut ItemFn,
+    ) -> syn::Result<Option<Self>> {
+        // Tests without generic parameters are left as they are
+        let has_own_params = has_generic_params(&item.sig.generics);
+        if opts.params.is_none() && !has_own_params {
+            if let Some(pos) = item
+                .attrs
+                .iter()
+                .position(|attr| attr.path().is_ident("generic_test"))
+            {
+                let attr = item.attrs.remove(pos);
+                return Err(Error::new_spanned(
+                    attr,
+                    "`generic_test` can only be used on functions with generic parameters",
+                ));
+            }
+            return Ok(None);
+        }
+        let fn_opts = extract_fn_opts(&mut item.attrs)?;
+        // So are the tests opting out of the parameters declared in `params()`
+        if let Some(no_params) = fn_opts.no_params() {
+            if opts.params.is_none() || has_own_params {
+                return Err(Error::new_spanned(
+                    no_params,
+                    "`no_params` can only be used on functions without generic parameters \
+                    in a suite declaring `params()`",
+                ));
+            }
+            if let Some(case) = fn_opts.cases().first() {
+                return Err(Error::new_spanned(
+                    &case.ident,
+                    "`case()` cannot be used with `no_params`",
+                ));
+            }
+            return Ok(None);
+        }
+        let test_attrs = extract_test_attrs(opts, &fn_opts, &mut item.attrs);
         if test_attrs.is_empty() {
             return Ok(None);
         }
+        let cases = fn_opts.into_cases();
+        if let Some(params) = &opts.params {
+            add_generic_params(&mut item.sig.generics, params)?;
+        }
         let sig = TestFnSignature::try_build(item)?;
-        Ok(Some(TestFn {
+        check_cases(&item.sig.ident, &sig, &cases)?;
+        let decl_sig = item.sig.clone();
+        strip_fixture_attrs(&mut item.sig);
+        // The test function can be called by instantiations elsewhere
+        // in the crate, or in other crates if the suite is exported
+        make_visible(opts, &mut item.vis);
+        let mut test_fn = TestFn::new(module_path, test_attrs, decl_sig, sig);
+        test_fn.cases = cases;
+        Ok(Some(test_fn))
+    }
+
+    fn new(
+        module_path: &[Ident],
+        test_attrs: Vec<Attribute>,
+        decl_sig: Signature,
+        sig: TestFnSignature,
+    ) -> Self {
+        TestFn {
+            module_path: module_path.to_vec(),
             test_attrs,
-            asyncness: item.sig.asyncness,
-            unsafety: item.sig.unsafety,
-            ident: item.sig.ident.clone(),
-            output: item.sig.output.clone(),
+            asyncness: decl_sig.asyncness,
+            unsafety: decl_sig.unsafety,
+            ident: decl_sig.ident.clone(),
+            output: decl_sig.output.clone(),
+            decl_sig,
             sig,
+            cases: Vec::new(),
+        }
+    }
+}
+
+impl TraitSuite {
+    fn try_extract(opts: &MacroOpts, item: &mut ItemTrait) -> syn::Result<Option<Self>> {
+        let mut errors = ErrorRecord::default();
+        let mut test_methods = Vec::new();
+        for trait_item in &mut item.items {
+            let method = match trait_item {
+                TraitItem::Fn(method) => method,
+                _ => continue,
+            };
+            let fn_opts = match extract_fn_opts(&mut method.attrs) {
+                Ok(fn_opts) => fn_opts,
+                Err(e) => {
+                    errors.add_error(e);
+                    continue;
+                }
+            };
+            let test_attrs = extract_test_attrs(opts, &fn_opts, &mut method.attrs);
+            if test_attrs.is_empty() {
+                continue;
+            }
+            if let Some(case) = fn_opts.cases().first() {
+                errors.add_error(Error::new_spanned(
+                    &case.ident,
+                    "test methods of a suite trait cannot have cases",
+                ));
+                continue;
+            }
+            if let Some(no_params) = fn_opts.no_params() {
+                errors.add_error(Error::new_spanned(
+                    no_params,
+                    "`no_params` cannot be used on test methods of a suite trait",
+                ));
+                continue;
+            }
+            let sig = &method.sig;
+            if !sig.inputs.is_empty() {
+                errors.add_error(Error::new_spanned(
+                    &sig.inputs,
+                    "test methods of a suite trait cannot have parameters",
+                ));
+                continue;
+            }
+            if !sig.generics.params.is_empty() {
+                errors.add_error(Error::new_spanned(
+                    &sig.generics,
+                    "test methods of a suite trait cannot have generic parameters; \
+                    use associated items of the trait instead",
+                ));
+                continue;
+            }
+            test_methods.push(TestMethod {
+                test_attrs,
+                asyncness: sig.asyncness,
+                unsafety: sig.unsafety,
+                ident: sig.ident.clone(),
+                output: sig.output.clone(),
+            });
+        }
+        errors.check()?;
+        if test_methods.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(TraitSuite {
+            ident: item.ident.clone(),
+            test_methods,
         }))
     }
 }
 
-fn extract_test_attrs(opts: &MacroOpts, item: &mut ItemFn) -> syn::Result<Vec<Attribute>> {
+fn has_generic_params(generics: &Generics) -> bool {
+    generics
+        .params
+        .iter()
+        .any(|param| !matches!(param, GenericParam::Lifetime(_)))
+}
+
+fn make_visible(opts: &MacroOpts, vis: &mut Visibility) {
+    match vis {
+        Visibility::Public(_) => {}
+        Visibility::Restricted(_) if !opts.export => {}
+        Visibility::Inherited if !opts.export => *vis = parse_quote! { pub(crate) },
+        _ => *vis = parse_quote! { pub },
+    }
+}
+
+// Adds the generic parameters declared in the macro attribute
+// to those of a test function
+fn add_generic_params(generics: &mut Generics, params: &Generics) -> syn::Result<()> {
+    let mut errors = ErrorRecord::default();
+    for param in &generics.params {
+        let ident = generic_param_ident(param);
+        if params
+            .params
+            .iter()
+            .any(|p| generic_param_ident(p) == ident)
+        {
+            errors.add_error(Error::new_spanned(
+                ident,
+                format!(
+                    "generic parameter `{}` is already declared in `params()`",
+                    ident
+                ),
+            ));
+        }
+    }
+    errors.check()?;
+    let fn_params = mem::take(&mut generics.params);
+    let (lifetimes, others): (Vec<_>, Vec<_>) = params
+        .params
+        .iter()
+        .cloned()
+        .chain(fn_params)
+        .partition(|param| matches!(param, GenericParam::Lifetime(_)));
+    generics.params = lifetimes.into_iter().chain(others).collect();
+    if generics.lt_token.is_none() {
+        generics.lt_token = Some(Default::default());
+        generics.gt_token = Some(Default::default());
+    }
+    if let Some(where_clause) = &params.where_clause {
+        generics
+            .make_where_clause()
+            .predicates
+            .extend(where_clause.predicates.iter().cloned());
+    }
+    Ok(())
+}
+
+fn generic_param_ident(param: &GenericParam) -> &Ident {
+    match param {
+        GenericParam::Lifetime(def) => &def.lifetime.ident,
+        GenericParam::Type(param) => &param.ident,
+        GenericParam::Const(param) => &param.ident,
+    }
+}
+
+fn strip_fixture_attrs(sig: &mut Signature) {
+    for input in &mut sig.inputs {
+        if let FnArg::Typed(arg) = input {
+            arg.attrs.retain(|attr| !signature::is_fixture_attr(attr));
+        }
+    }
+}
+
+// Removes the attribute marking a setup or teardown function, if present
+fn take_hook_attr(attrs: &mut Vec<Attribute>) -> syn::Result<Option<HookKind>> {
+    let mut found = None;
+    let mut pos = 0;
+    while pos < attrs.len() {
+        let kind = HookKind::ALL
+            .into_iter()
+            .find(|kind| attrs[pos].path().is_ident(kind.attr_name()));
+        let kind = match kind {
+            Some(kind) => kind,
+            None => {
+                pos += 1;
+                continue;
+            }
+        };
+        let attr = attrs.remove(pos);
+        attr.meta.require_path_only()?;
+        if found.is_some() {
+            return Err(Error::new_spanned(
+                attr,
+                "a function can only have one `generic_setup` or `generic_teardown` attribute",
+            ));
+        }
+        found = Some(kind);
+    }
+    Ok(found)
+}
+
+// Checks that each case gives the values for all arguments of the test
+// other than fixtures
+fn check_cases(ident: &Ident, sig: &TestFnSignature, cases: &[TestCase]) -> syn::Result<()> {
+    let mut errors = ErrorRecord::default();
+    let arg_count = sig.input.args.len();
+    for case in cases {
+        if case.values.len() != arg_count {
+            errors.add_error(Error::new_spanned(
+                &case.ident,
+                format!(
+                    "test function `{}` takes {} arguments, but the case gives {} values",
+                    ident,
+                    arg_count,
+                    case.values.len(),
+                ),
+            ));
+        }
+    }
+    errors.check()
+}
+
+// Extracts the options given in `generic_test` attributes, if any
+fn extract_fn_opts(attrs: &mut Vec<Attribute>) -> syn::Result<TestFnOpts> {
     let mut fn_opts = TestFnOpts::default();
     let mut pos = 0;
-    while pos < item.attrs.len() {
-        let attr = &item.attrs[pos];
+    while pos < attrs.len() {
+        let attr = &attrs[pos];
         if attr.meta.path().is_ident("generic_test") {
-            let attr = item.attrs.remove(pos);
+            let attr = attrs.remove(pos);
             fn_opts.apply_attr(attr.meta)?;
             continue;
         }
         pos += 1;
     }
+    Ok(fn_opts)
+}
+
+// Extracts the test attributes, along with the attributes copied
+// to the instantiated tests
+fn extract_test_attrs(
+    opts: &MacroOpts,
+    fn_opts: &TestFnOpts,
+    attrs: &mut Vec<Attribute>,
+) -> Vec<Attribute> {
     let mut test_attrs = Vec::new();
     let mut pos = 0;
-    while pos < item.attrs.len() {
-        let attr = &item.attrs[pos];
-        if options::is_test_attr(attr, opts, &fn_opts) {
-            test_attrs.push(item.attrs.remove(pos));
+    while pos < attrs.len() {
+        let attr = &attrs[pos];
+        if options::is_test_attr(attr, opts, fn_opts) {
+            test_attrs.push(attrs.remove(pos));
             continue;
         }
         pos += 1;
     }
     if !test_attrs.is_empty() {
-        for attr in &item.attrs {
-            if options::is_copied_attr(attr, opts, &fn_opts) {
+        for attr in attrs.iter() {
+            if options::is_copied_attr(attr, opts, fn_opts) {
                 test_attrs.push(attr.clone());
             }
         }
     }
-    Ok(test_attrs)
-}
-
-fn generic_arity(generics: &Generics) -> usize {
-    generics
-        .params
-        .iter()
-        .filter(|param| match param {
-            GenericParam::Type(_) | GenericParam::Const(_) => true,
-            GenericParam::Lifetime(_) => false,
-        })
-        .count()
-}
-
-pub struct InstArguments(Punctuated<GenericArgument, Token![,]>);
-
-impl InstArguments {
-    pub fn try_extract(item: &mut ItemMod) -> syn::Result<Option<Self>> {
-        for (pos, attr) in item.attrs.iter().enumerate() {
-            if attr.meta.path().is_ident("instantiate_tests") {
-                match attr.style {
-                    AttrStyle::Outer => {}
-                    AttrStyle::Inner(_) => {
-                        return Err(Error::new_spanned(attr, "cannot be an inner attribute"))
-                    }
-                };
-                let AngleBracketedGenericArguments { args, .. } = attr.parse_args()?;
-                item.attrs.remove(pos);
-                return Ok(Some(InstArguments(args)));
-            }
-        }
-        Ok(None)
-    }
-}
-
-impl ToTokens for InstArguments {
-    fn to_tokens(&self, tokens: &mut TokenStream) {
-        self.0.to_tokens(tokens)
-    }
+    test_attrs
 }
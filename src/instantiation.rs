@@ -0,0 +1,511 @@
+use crate::error::ErrorRecord;
+use crate::extract::TestFn;
+use crate::naming;
+use crate::options::InstOpts;
+
+use proc_macro2::{Group, TokenStream};
+use quote::{quote, ToTokens};
+use syn::meta;
+use syn::parse::{Parse, ParseStream, Parser};
+use syn::punctuated::Punctuated;
+use syn::{parenthesized, token, Token};
+use syn::{
+    AngleBracketedGenericArguments, AssocConst, AssocType, AttrStyle, Error, Expr, GenericArgument,
+    Ident, ItemMod, Type,
+};
+
+#[derive(Clone)]
+pub struct InstArguments {
+    bindings: Vec<InstBinding>,
+}
+
+// Instantiation arguments resolved for a test function
+pub struct ResolvedArgs {
+    // Generic arguments for the call
+    pub args: Punctuated<GenericArgument, Token![,]>,
+    // Indices of the arguments bound to each type or const parameter
+    pub param_bindings: Vec<usize>,
+}
+
+// An instantiation argument, optionally bound to a generic parameter by name
+#[derive(Clone)]
+struct InstBinding {
+    name: Option<Ident>,
+    value: GenericArgument,
+}
+
+// Lists of alternative arguments for each generic parameter position,
+// instantiated as the cartesian product.
+pub struct InstMatrix {
+    tokens: TokenStream,
+    positions: Vec<MatrixPosition>,
+}
+
+struct MatrixPosition {
+    name: Option<Ident>,
+    alternatives: Vec<GenericArgument>,
+}
+
+pub enum InstSpec {
+    Args(InstArguments),
+    Matrix(InstMatrix),
+}
+
+// The arguments of an `instantiate_tests` attribute
+pub struct InstAttrArgs {
+    pub spec: InstSpec,
+    pub opts: InstOpts,
+}
+
+// An instantiation listed in the `define` attribute, to be expanded
+// into a generated submodule of the root module.
+pub struct RootInstantiation {
+    pub name: Option<Ident>,
+    pub spec: InstSpec,
+    pub opts: InstOpts,
+}
+
+impl InstAttrArgs {
+    pub fn try_extract(item: &mut ItemMod) -> syn::Result<Option<Self>> {
+        for (pos, attr) in item.attrs.iter().enumerate() {
+            if attr.meta.path().is_ident("instantiate_tests") {
+                match attr.style {
+                    AttrStyle::Outer => {}
+                    AttrStyle::Inner(_) => {
+                        return Err(Error::new_spanned(attr, "cannot be an inner attribute"))
+                    }
+                };
+                let args = attr.parse_args()?;
+                item.attrs.remove(pos);
+                return Ok(Some(args));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Parse for InstSpec {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let lookahead = input.lookahead1();
+        if lookahead.peek(Token![<]) {
+            let AngleBracketedGenericArguments { args, .. } = input.parse()?;
+            let bindings = args
+                .into_iter()
+                .map(InstBinding::try_from_arg)
+                .collect::<syn::Result<_>>()?;
+            Ok(InstSpec::Args(InstArguments::try_new(bindings)?))
+        } else if lookahead.peek(kw::matrix) {
+            input.parse::<kw::matrix>()?;
+            let content;
+            parenthesized!(content in input);
+            let tokens = content.fork().parse::<TokenStream>()?;
+            let positions = content.parse_terminated(MatrixPosition::parse, Token![,])?;
+            let matrix = InstMatrix {
+                tokens,
+                positions: positions.into_iter().collect(),
+            };
+            matrix.validate()?;
+            Ok(InstSpec::Matrix(matrix))
+        } else {
+            Err(lookahead.error())
+        }
+    }
+}
+
+impl Parse for InstAttrArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let spec = input.parse()?;
+        let mut opts = InstOpts::default();
+        if !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            let opts_parser = meta::parser(|meta| opts.parse(meta));
+            opts_parser.parse2(input.parse()?)?;
+        }
+        Ok(InstAttrArgs { spec, opts })
+    }
+}
+
+impl Parse for RootInstantiation {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = if input.peek(Ident) && input.peek2(Token![=]) {
+            let name = input.parse()?;
+            input.parse::<Token![=]>()?;
+            Some(name)
+        } else {
+            None
+        };
+        let spec = input.parse()?;
+        // Options follow the arguments without a separator,
+        // as the entries in the list are separated by commas
+        let mut opts = InstOpts::default();
+        loop {
+            let option = if input.peek(Ident) && input.peek2(token::Paren) {
+                let ident: Ident = input.parse()?;
+                let group: Group = input.parse()?;
+                quote! { #ident #group }
+            } else if input.peek(Ident) && input.peek2(Token![=]) {
+                let ident: Ident = input.parse()?;
+                let eq: Token![=] = input.parse()?;
+                let value: Expr = input.parse()?;
+                quote! { #ident #eq #value }
+            } else {
+                break;
+            };
+            let opts_parser = meta::parser(|meta| opts.parse(meta));
+            opts_parser.parse2(option)?;
+        }
+        Ok(RootInstantiation { name, spec, opts })
+    }
+}
+
+impl RootInstantiation {
+    pub fn mod_name(&self) -> syn::Result<Ident> {
+        match (&self.name, &self.spec) {
+            (Some(name), _) => Ok(name.clone()),
+            (None, InstSpec::Args(args)) => args.mod_name(),
+            (None, InstSpec::Matrix(matrix)) => Err(Error::new_spanned(
+                matrix,
+                "matrix instantiation must be given a module name",
+            )),
+        }
+    }
+}
+
+impl InstBinding {
+    fn try_from_arg(arg: GenericArgument) -> syn::Result<Self> {
+        let (name, value) = match arg {
+            GenericArgument::AssocType(AssocType {
+                ident,
+                generics: None,
+                ty,
+                ..
+            }) => (Some(ident), GenericArgument::Type(ty)),
+            GenericArgument::AssocConst(AssocConst {
+                ident,
+                generics: None,
+                value,
+                ..
+            }) => (Some(ident), GenericArgument::Const(value)),
+            arg @ (GenericArgument::Lifetime(_)
+            | GenericArgument::Type(_)
+            | GenericArgument::Const(_)) => (None, arg),
+            arg => {
+                return Err(Error::new_spanned(
+                    arg,
+                    "unsupported form of instantiation argument",
+                ))
+            }
+        };
+        Ok(InstBinding { name, value })
+    }
+}
+
+impl InstBinding {
+    fn is_placeholder(&self) -> bool {
+        matches!(self.value, GenericArgument::Type(Type::Infer(_)))
+    }
+
+    // Fills a placeholder with the value of a binding from a nested
+    // instantiation. Placeholders in the nested binding are ignored.
+    fn fill(&mut self, nested: &InstBinding) -> syn::Result<()> {
+        if nested.is_placeholder() {
+            return Ok(());
+        }
+        if !self.is_placeholder() {
+            return Err(Error::new_spanned(
+                nested,
+                "argument is already given by the enclosing instantiation",
+            ));
+        }
+        self.value = nested.value.clone();
+        Ok(())
+    }
+}
+
+impl InstArguments {
+    fn try_new(bindings: Vec<InstBinding>) -> syn::Result<Self> {
+        let mut iter = bindings.iter();
+        if let Some(first) = iter.next() {
+            let is_named = first.name.is_some();
+            let mut names = vec![&first.name];
+            for binding in iter {
+                if binding.name.is_some() != is_named {
+                    return Err(Error::new_spanned(
+                        binding,
+                        "positional and named instantiation arguments cannot be mixed",
+                    ));
+                }
+                if is_named && names.contains(&&binding.name) {
+                    return Err(Error::new_spanned(
+                        &binding.name,
+                        "generic parameter is bound more than once",
+                    ));
+                }
+                names.push(&binding.name);
+            }
+        }
+        Ok(InstArguments { bindings })
+    }
+
+    fn is_named(&self) -> bool {
+        self.bindings
+            .first()
+            .is_some_and(|binding| binding.name.is_some())
+    }
+
+    // Resolves the generic arguments for a call to the test function.
+    // Positional arguments are passed through as given, while named
+    // arguments are matched to the generic parameters of the function.
+    pub fn resolve_for(&self, test: &TestFn) -> syn::Result<ResolvedArgs> {
+        let generic_params = &test.sig.generic_params;
+        if !self.is_named() {
+            let param_bindings = self
+                .bindings
+                .iter()
+                .enumerate()
+                .filter(|(_, b)| !matches!(b.value, GenericArgument::Lifetime(_)))
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>();
+            if param_bindings.len() != generic_params.len() {
+                return Err(Error::new_spanned(
+                    self,
+                    format!(
+                        "test function `{}` has {} generic parameters, \
+                        but {} positional arguments are given; \
+                        use named arguments to instantiate functions \
+                        with different parameters",
+                        test.ident,
+                        generic_params.len(),
+                        param_bindings.len(),
+                    ),
+                ));
+            }
+            return Ok(ResolvedArgs {
+                args: self.bindings.iter().map(|b| b.value.clone()).collect(),
+                param_bindings,
+            });
+        }
+        let param_bindings = generic_params
+            .iter()
+            .map(|param| {
+                self.bindings
+                    .iter()
+                    .position(|binding| binding.name.as_ref() == Some(&param.ident))
+                    .ok_or_else(|| {
+                        Error::new_spanned(
+                            self,
+                            format!(
+                                "no argument is given for generic parameter `{}` \
+                                of test function `{}`",
+                                param.ident, test.ident
+                            ),
+                        )
+                    })
+            })
+            .collect::<syn::Result<Vec<_>>>()?;
+        Ok(ResolvedArgs {
+            args: param_bindings
+                .iter()
+                .map(|&i| self.bindings[i].value.clone())
+                .collect(),
+            param_bindings,
+        })
+    }
+
+    // Checks if any of the arguments is a `_` placeholder to be filled
+    // by nested instantiations
+    pub fn is_partial(&self) -> bool {
+        self.bindings.iter().any(InstBinding::is_placeholder)
+    }
+
+    // Fills the placeholders in the arguments of an enclosing partial
+    // instantiation with these arguments.
+    pub fn complete(&self, outer: &InstArguments) -> syn::Result<InstArguments> {
+        if !outer.bindings.is_empty()
+            && !self.bindings.is_empty()
+            && self.is_named() != outer.is_named()
+        {
+            return Err(Error::new_spanned(
+                self,
+                "positional and named arguments cannot be mixed \
+                with those of the enclosing partial instantiation",
+            ));
+        }
+        let mut bindings = outer.bindings.clone();
+        if self.is_named() {
+            for binding in &self.bindings {
+                match bindings.iter_mut().find(|b| b.name == binding.name) {
+                    Some(outer_binding) => outer_binding.fill(binding)?,
+                    None => bindings.push(binding.clone()),
+                }
+            }
+        } else {
+            if self.bindings.len() != bindings.len() {
+                return Err(Error::new_spanned(
+                    self,
+                    format!(
+                        "expected {} arguments to complete the enclosing partial instantiation, \
+                        found {}",
+                        bindings.len(),
+                        self.bindings.len(),
+                    ),
+                ));
+            }
+            for (outer_binding, binding) in bindings.iter_mut().zip(&self.bindings) {
+                outer_binding.fill(binding)?;
+            }
+        }
+        Ok(InstArguments { bindings })
+    }
+
+    pub fn value(&self, index: usize) -> &GenericArgument {
+        &self.bindings[index].value
+    }
+
+    pub fn set_value(&mut self, index: usize, value: GenericArgument) {
+        self.bindings[index].value = value;
+    }
+
+    // Checks if the argument at `index` is given rather than left
+    // as a placeholder
+    pub fn is_given(&self, index: usize) -> bool {
+        !self.bindings[index].is_placeholder()
+    }
+
+    // Checks that each named argument is used by some of the test functions
+    pub fn check_bindings_used(&self, tests: &[&TestFn]) -> syn::Result<()> {
+        let mut errors = ErrorRecord::default();
+        for name in self.bindings.iter().filter_map(|b| b.name.as_ref()) {
+            let is_used = tests
+                .iter()
+                .any(|test| test.sig.generic_params.iter().any(|p| p.ident == *name));
+            if !is_used {
+                errors.add_error(Error::new_spanned(
+                    name,
+                    format!("no test function has a generic parameter `{}`", name),
+                ));
+            }
+        }
+        errors.check()
+    }
+
+    pub fn mod_name(&self) -> syn::Result<Ident> {
+        let values = self
+            .bindings
+            .iter()
+            .filter(|binding| !binding.is_placeholder())
+            .map(|binding| &binding.value)
+            .collect::<Punctuated<_, Token![,]>>();
+        naming::derive_mod_name(&values)
+    }
+}
+
+impl Parse for MatrixPosition {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        input.parse::<Token![<]>()?;
+        let name = if input.peek(Ident) && input.peek2(Token![=]) {
+            let name = input.parse()?;
+            input.parse::<Token![=]>()?;
+            Some(name)
+        } else {
+            None
+        };
+        let mut alternatives = Vec::new();
+        loop {
+            alternatives.push(input.parse()?);
+            if input.peek(Token![>]) {
+                break;
+            }
+            input.parse::<Token![|]>()?;
+        }
+        input.parse::<Token![>]>()?;
+        Ok(MatrixPosition { name, alternatives })
+    }
+}
+
+mod kw {
+    syn::custom_keyword!(matrix);
+}
+
+impl InstMatrix {
+    fn validate(&self) -> syn::Result<()> {
+        if self.positions.is_empty() {
+            return Err(Error::new_spanned(
+                self,
+                "matrix must list alternatives for at least one parameter",
+            ));
+        }
+        // Check the bindings for consistency
+        InstArguments::try_new(
+            self.positions
+                .iter()
+                .map(|pos| InstBinding {
+                    name: pos.name.clone(),
+                    value: pos.alternatives[0].clone(),
+                })
+                .collect(),
+        )?;
+        Ok(())
+    }
+
+    pub fn combination_count(&self) -> Option<usize> {
+        self.positions
+            .iter()
+            .try_fold(1usize, |acc, pos| acc.checked_mul(pos.alternatives.len()))
+    }
+
+    pub fn combinations(&self) -> Vec<InstArguments> {
+        let mut combinations = vec![Vec::new()];
+        for pos in &self.positions {
+            combinations = combinations
+                .into_iter()
+                .flat_map(|bindings: Vec<InstBinding>| {
+                    pos.alternatives.iter().map(move |alt| {
+                        let mut bindings = bindings.clone();
+                        bindings.push(InstBinding {
+                            name: pos.name.clone(),
+                            value: alt.clone(),
+                        });
+                        bindings
+                    })
+                })
+                .collect();
+        }
+        combinations
+            .into_iter()
+            .map(|bindings| InstArguments { bindings })
+            .collect()
+    }
+}
+
+impl ToTokens for InstMatrix {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        self.tokens.to_tokens(tokens)
+    }
+}
+
+impl ToTokens for InstSpec {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        match self {
+            InstSpec::Args(args) => args.to_tokens(tokens),
+            InstSpec::Matrix(matrix) => matrix.to_tokens(tokens),
+        }
+    }
+}
+
+impl ToTokens for InstBinding {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        if let Some(name) = &self.name {
+            name.to_tokens(tokens);
+            <Token![=]>::default().to_tokens(tokens);
+        }
+        self.value.to_tokens(tokens);
+    }
+}
+
+impl ToTokens for InstArguments {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let bindings = self.bindings.iter().collect::<Punctuated<_, Token![,]>>();
+        bindings.to_tokens(tokens)
+    }
+}
@@ -14,33 +14,62 @@
 #![warn(clippy::all)]
 #![warn(future_incompatible)]
 #![warn(missing_docs)]
+// The doc examples define test functions only to show them being instantiated
+#![allow(clippy::test_attr_in_doctest)]
 
 mod error;
 mod expand;
+mod external;
 mod extract;
+mod instantiation;
+mod module_file;
+mod naming;
 mod options;
 mod signature;
 
 use options::ParsedMacroOpts;
 use proc_macro::TokenStream;
 use syn::parse_macro_input;
-use syn::{meta, ItemMod};
+use syn::{meta, Item, ItemMod};
 
 /// Populates a module tree with test cases parameterizing generic definitions.
 ///
 /// This macro is used to annotate a module containing test case definitions.
-/// All functions defined directly in the module and marked with
-/// a [test attribute][test-attributes] must have the same number and order
-/// of generic type parameters.
-///
-/// Empty submodules defined inline at any depth under the module on which
-/// the macro is invoked can be annotated with the `instantiate_tests`
-/// attribute. The macro populates these submodules with functions having names,
+/// Functions defined in the module and marked with
+/// a [test attribute][test-attributes] are generic test functions.
+/// Generic test functions can also be defined in
+/// [submodules](#nested-suites) of the module.
+/// When instantiated with positional arguments, all of them must have
+/// the same number and order of generic type parameters; with
+/// [named arguments](#named-arguments), each function can take any subset
+/// of the parameters named in the instantiation.
+/// Test functions without generic type or const parameters are ordinary
+/// tests: they are left in the module as they are and not instantiated,
+/// unless the module declares [parameters shared by all
+/// tests](#parameters-shared-by-all-tests).
+///
+/// Submodules at any depth under the module on which the macro is invoked
+/// can be annotated with the `instantiate_tests` attribute. The macro
+/// populates these submodules with functions having names,
 /// signatures, and test attributes mirroring the generic test functions at the
 /// macro invocation's root module. Each of the instantiated functions calls
 /// its generic namesake in the root module, parameterized with the arguments
 /// given in `instantiate_tests`.
 ///
+/// The instantiated functions are appended after any items already defined
+/// in the submodule, such as helper types for the instantiation; an error
+/// is reported if any of these items has the same name as a generated
+/// function. The arguments are resolved in the scope enclosing the
+/// submodule, so its own items are referred to with the submodule's name
+/// as in `#[instantiate_tests(<local::Helper>)] mod local { ... }`.
+/// A submodule declared without a body, as in `mod foo;`, is made inline.
+/// As with [modules in separate files](#modules-in-separate-files),
+/// such a declaration in the input of the macro is only accepted by
+/// a nightly toolchain with the `proc_macro_hygiene` feature; stable Rust
+/// rejects it with error E0658. The declaration also needs to be marked
+/// with `#[rustfmt::skip]`, as rustfmt would otherwise look for
+/// the module's file.
+///
 /// # Basic example
 ///
 /// ```
@@ -167,6 +196,668 @@ use syn::{meta, ItemMod};
 /// Finally, all function parameter attributes on the generic test functions
 /// are always copied into the signatures of the instantiated functions.
 ///
+/// # Named arguments
+///
+/// Instead of listing them positionally, the arguments in `instantiate_tests`
+/// can be bound to the generic parameters by name. Each test function is then
+/// called with the arguments matched to its own parameters, so the order in
+/// which the parameters are declared does not matter.
+///
+/// ```
+/// #[generic_tests::define]
+/// mod tests {
+///     #[test]
+///     fn fill_vec<T: Clone + Default, const LEN: usize>() {
+///         let v = vec![T::default(); LEN];
+///         assert_eq!(v.len(), LEN);
+///     }
+///
+///     #[test]
+///     fn fill_array<const LEN: usize, T: Copy + Default>() {
+///         let a = [T::default(); LEN];
+///         assert_eq!(a.len(), LEN);
+///     }
+///
+///     #[instantiate_tests(<T = u8, LEN = 16>)]
+///     mod u8_16 {}
+/// }
+/// # fn main() {}
+/// ```
+///
+/// A test function only needs to declare the parameters it uses, as long as
+/// all of them are bound in each instantiation.
+///
+/// ```
+/// #[generic_tests::define]
+/// mod tests {
+///     #[test]
+///     fn default_is_empty<T: Default + AsRef<[u8]>>() {
+///         assert!(T::default().as_ref().is_empty());
+///     }
+///
+///     #[test]
+///     fn with_capacity<const CAP: usize>() {
+///         let v = Vec::<u8>::with_capacity(CAP);
+///         assert!(v.capacity() >= CAP);
+///     }
+///
+///     #[instantiate_tests(<T = Vec<u8>, CAP = 4096>)]
+///     mod vec_4096 {}
+/// }
+/// # fn main() {}
+/// ```
+///
+/// Named and positional arguments cannot be mixed in one instantiation.
+///
+/// # Partial instantiation
+///
+/// Some of the arguments in `instantiate_tests` can be given as `_`
+/// placeholders, to be filled by the arguments of `instantiate_tests`
+/// attributes on nested modules. No tests are generated in a module with
+/// a partial instantiation; its nested instantiations are completed with
+/// the arguments given for the enclosing module, and each argument can only
+/// be given once on the way down the module tree.
+///
+/// ```
+/// #[generic_tests::define]
+/// mod tests {
+///     #[test]
+///     fn fill<T: Clone + Default, const N: usize>() {
+///         assert_eq!(vec![T::default(); N].len(), N);
+///     }
+///
+///     #[instantiate_tests(<String, _>)]
+///     mod string {
+///         #[instantiate_tests(<_, 16>)]
+///         mod n16 {}
+///
+///         #[instantiate_tests(<_, 4096>)]
+///         mod n4096 {}
+///     }
+/// }
+/// # fn main() {}
+/// ```
+///
+/// With named arguments, a parameter is left to nested instantiations
+/// with a placeholder binding like `N = _`. Only the `cfg()` option
+/// can be given for a partial instantiation.
+///
+/// As with any instantiation, each argument is resolved in the scope
+/// enclosing the module where it is given, so items declared in a module
+/// with a partial instantiation do not affect its arguments. The arguments
+/// of the nested instantiations are resolved within that module, where
+/// names from the outer scope need to be imported to be used.
+///
+/// # Selecting tests to instantiate
+///
+/// By default, all generic test functions are instantiated in each module.
+/// The arguments in `instantiate_tests` can be followed by an `only()` list
+/// naming the test functions to instantiate, or an `except()` list naming
+/// the test functions to leave out.
+///
+/// ```
+/// #[generic_tests::define]
+/// mod tests {
+///     #[test]
+///     fn from_bytes<T: From<&'static [u8]> + AsRef<[u8]>>() {
+///         assert_eq!(T::from(b"abc").as_ref(), b"abc");
+///     }
+///
+///     #[test]
+///     fn extend<T: Default + Extend<u8> + AsRef<[u8]>>() {
+///         let mut v = T::default();
+///         v.extend([1, 2]);
+///         assert_eq!(v.as_ref(), [1, 2]);
+///     }
+///
+///     #[instantiate_tests(<Vec<u8>>)]
+///     mod vec {}
+///
+///     #[instantiate_tests(<&'static [u8]>, except(extend))]
+///     mod slice {}
+/// }
+/// # fn main() {}
+/// ```
+///
+/// The test attributes of individual functions can also be amended for
+/// one instantiation: `ignore(test_name)` or `ignore(test_name = "reason")`
+/// marks the instantiated function with `#[ignore]`, and
+/// `should_panic(test_name)` or `should_panic(test_name = "expected")`
+/// with `#[should_panic]`. These replace an attribute with the same name
+/// on the generic test function.
+///
+/// ```
+/// #[generic_tests::define]
+/// mod tests {
+///     use std::fmt::Debug;
+///     use std::str::FromStr;
+///
+///     #[test]
+///     fn parse_300<T: FromStr>()
+///     where
+///         T::Err: Debug,
+///     {
+///         "300".parse::<T>().unwrap();
+///     }
+///
+///     #[instantiate_tests(<u32>)]
+///     mod u32 {}
+///
+///     #[instantiate_tests(<u8>, should_panic(parse_300))]
+///     mod u8 {}
+///
+///     #[instantiate_tests(<bool>, ignore(parse_300 = "not a number"))]
+///     mod bool {}
+/// }
+/// # fn main() {}
+/// ```
+///
+/// Test attributes can also be supplied for all functions in an instantiation
+/// with the `attrs()` option. Each attribute listed there replaces
+/// any attribute with the same path on the generic test functions, so that,
+/// for example, the same async test suite can be run on different runtimes.
+///
+/// ```
+/// #[generic_tests::define(attrs(tokio::test))]
+/// mod tests {
+///     #[tokio::test]
+///     async fn yields<T>() {
+///         tokio::task::yield_now().await;
+///     }
+///
+///     #[instantiate_tests(<()>)]
+///     mod current_thread {}
+///
+///     #[instantiate_tests(<()>, attrs(tokio::test(flavor = "multi_thread")))]
+///     mod multi_thread {}
+/// }
+/// # fn main() {}
+/// ```
+///
+/// # Conditional instantiation
+///
+/// The `cfg()` option puts the instantiation module under a `cfg` attribute
+/// with the given predicate. Individual test functions in the instantiation
+/// can be gated with `test_cfg(test_name(predicate), ...)`.
+///
+/// ```
+/// #[generic_tests::define]
+/// mod tests {
+///     #[test]
+///     fn is_empty_by_default<T: Default + AsRef<[u8]>>() {
+///         assert!(T::default().as_ref().is_empty());
+///     }
+///
+///     #[instantiate_tests(<bytes::Bytes>, cfg(unix))]
+///     mod shared {}
+///
+///     #[instantiate_tests(<Vec<u8>>, test_cfg(is_empty_by_default(not(miri))))]
+///     mod vec {}
+/// }
+/// # fn main() {}
+/// ```
+///
+/// # Instantiation in the macro attribute
+///
+/// As a more concise alternative to empty submodules, instantiations can be
+/// listed in the `instantiate()` parameter of the `define` attribute.
+/// The macro generates a submodule of the annotated module for each entry.
+/// An entry can be given a module name; otherwise the name is derived from
+/// the snake-cased arguments, e.g. `vec_u8` for `<Vec<u8>>`.
+///
+/// ```
+/// #[generic_tests::define(instantiate(string = <String>, <Vec<u8>>))]
+/// mod tests {
+///     #[test]
+///     fn is_empty_by_default<T: Default + PartialEq>() {
+///         assert!(T::default() == T::default());
+///     }
+/// }
+/// # fn main() {}
+/// ```
+///
+/// The arguments are resolved in the scope of the annotated module.
+/// Options such as `cfg()` or `only()` follow the arguments of an entry
+/// without a comma, e.g. `instantiate(bytes = <Bytes> cfg(unix), <Vec<u8>>)`.
+/// The `fixture = expr` option is given in the same way, as in
+/// `instantiate(<Vec<u8>> fixture = Vec::new() only(push))`.
+///
+/// # Matrix instantiation
+///
+/// Instead of a single list of arguments, the `instantiate_tests` attribute
+/// can be given a `matrix()` of alternatives for each generic parameter,
+/// separated with `|`. A submodule is generated for each combination of
+/// the alternatives, named after the snake-cased arguments.
+///
+/// ```
+/// #[generic_tests::define]
+/// mod tests {
+///     use std::borrow::Cow;
+///     use std::fmt::Display;
+///
+///     #[test]
+///     fn print_with_len<S, L>()
+///     where
+///         S: From<&'static str> + Display,
+///         L: From<u8> + Display,
+///     {
+///         let s = S::from("Hello, world!");
+///         let len = L::from(13);
+///         println!("{} ({})", s, len);
+///     }
+///
+///     // Generates submodules `string_u32`, `string_u64`,
+///     // `cow_static_str_u32`, and `cow_static_str_u64`
+///     #[instantiate_tests(matrix(<String | Cow<'static, str>>, <u32 | u64>))]
+///     mod matrix {}
+/// }
+/// # fn main() {}
+/// ```
+///
+/// To guard against accidental combinatorial explosion, the number of
+/// combinations in a matrix is limited to 64 by default. The limit can be
+/// changed with the `matrix_limit` parameter of the `define` attribute,
+/// e.g. `#[generic_tests::define(matrix_limit = 256)]`.
+///
+/// The parameter positions in a matrix can be named as well, e.g.
+/// `matrix(<T = String | Cow<'static, str>>, <LEN = 16 | 4096>)`.
+///
+/// A matrix can also be listed in the `instantiate()` parameter of
+/// the `define` attribute, in which case it must be given a module name:
+/// `instantiate(matrix = matrix(<String | Cow<'static, str>>, <u32 | u64>))`.
+///
+/// # Generic parameters in signatures
+///
+/// The generic parameters of a test function can be used in the types of
+/// its parameters and its return value. In the signatures of the instantiated
+/// functions, they are replaced with the instantiation arguments.
+/// An associated type of a type parameter, such as `T::Err` below, is
+/// resolved with the single trait bound of the parameter; if the parameter
+/// has more than one trait bound, use a qualified path like
+/// `<T as FromStr>::Err`.
+///
+/// ```
+/// #[generic_tests::define]
+/// mod tests {
+///     use std::str::FromStr;
+///
+///     #[test]
+///     fn parse<T: FromStr>() -> Result<(), T::Err> {
+///         let _: T = "42".parse()?;
+///         Ok(())
+///     }
+///
+///     #[instantiate_tests(<u32>)]
+///     mod u32 {}
+/// }
+/// # fn main() {}
+/// ```
+///
+/// # Nested suites
+///
+/// Generic test functions are also collected from the submodules of
+/// the macro's root module that are not annotated with `instantiate_tests`.
+/// Each instantiation module gets the same tree of submodules
+/// containing the instantiated tests, so the tests in the example below
+/// have paths like `tests::string::read::exact_fill`. Attributes of
+/// the submodules that are [copied](#test-attributes) to instantiated
+/// test functions, such as `cfg`, are copied to the replicated submodules.
+///
+/// Private test functions and submodules are made visible in the crate
+/// so that the instantiated tests can call them. Other items
+/// of the submodules that the instantiated tests refer to in their
+/// signatures need to be visible in the root module as well.
+///
+/// ```
+/// #[generic_tests::define]
+/// mod tests {
+///     mod read {
+///         #[test]
+///         fn exact_fill<T: AsRef<[u8]> + From<&'static str>>() {
+///             let s = T::from("Hello");
+///             assert_eq!(s.as_ref().len(), 5);
+///         }
+///     }
+///
+///     mod write {
+///         #[test]
+///         fn push<T: Default + Extend<char>>() {
+///             let mut s = T::default();
+///             s.extend(Some('a'));
+///         }
+///     }
+///
+///     #[instantiate_tests(<String>)]
+///     mod string {}
+/// }
+/// # fn main() {}
+/// ```
+///
+/// # Trait-based suites
+///
+/// A suite can also be written as a trait with test methods, which use
+/// associated items of the trait in place of generic parameters.
+/// Each implementation of the trait in the module tree stands in for
+/// an instantiation: a submodule named after the snake-cased implementing
+/// type and trait is added next to the implementation, containing test functions
+/// that call the test methods as implemented for the type. An
+/// implementation can override individual test methods, for example to
+/// add checks specific to the type.
+///
+/// Test methods have no parameters and no generic parameters.
+/// The implementations of a suite trait cannot be generic.
+/// An implementation is recognized by the trait being named as `Trait`
+/// or `self::Trait`, so implementations of other traits with the same
+/// name, such as `impl std::io::Read for ...` next to a suite trait
+/// named `Read`, are left alone.
+///
+/// ```
+/// #[generic_tests::define]
+/// mod tests {
+///     trait StackContract {
+///         type Stack: Default + Extend<u8> + IntoIterator<Item = u8>;
+///
+///         #[test]
+///         fn extend() {
+///             let mut stack = Self::Stack::default();
+///             stack.extend([1, 2]);
+///             assert_eq!(stack.into_iter().count(), 2);
+///         }
+///     }
+///
+///     struct VecStack;
+///
+///     // Generates submodule `vec_stack_stack_contract` with test function `extend`
+///     impl StackContract for VecStack {
+///         type Stack = Vec<u8>;
+///     }
+/// }
+/// # fn main() {}
+/// ```
+///
+/// # Including other suites
+///
+/// The tests of other suites can be included in a suite with the `include()`
+/// parameter of the `define` attribute, listing the paths to the modules
+/// annotated with `define`, as resolved in the annotated module. The tests
+/// of each included suite are instantiated in a submodule named after
+/// the last segment of its path, so the tests in the example below have
+/// paths like `file_like::vec_deque::read::read_to_end`.
+///
+/// ```
+/// #[generic_tests::define]
+/// mod read {
+///     use std::io::Read;
+///
+///     #[test]
+///     fn read_to_end<T: Read + From<Vec<u8>>>() {
+///         let mut buf = Vec::new();
+///         T::from(b"Hello".to_vec()).read_to_end(&mut buf).unwrap();
+///         assert_eq!(buf, b"Hello");
+///     }
+/// }
+///
+/// #[generic_tests::define]
+/// mod write {
+///     use std::io::Write;
+///
+///     #[test]
+///     fn write_all<T: Write + From<Vec<u8>>>() {
+///         T::from(Vec::new()).write_all(b"Hello").unwrap();
+///     }
+/// }
+///
+/// #[generic_tests::define(include(super::read, super::write))]
+/// mod file_like {
+///     use std::collections::VecDeque;
+///
+///     #[instantiate_tests(<VecDeque<u8>>)]
+///     mod vec_deque {}
+/// }
+/// # fn main() {}
+/// ```
+///
+/// As with [instantiation outside of the suite](#instantiation-outside-of-the-suite),
+/// items of the included suites that are referred to in the test function
+/// signatures need to be visible in the crate.
+///
+/// # Instantiation outside of the suite
+///
+/// The tests can also be instantiated in modules elsewhere in the crate,
+/// such as next to the types they test, using the [`instantiate`]
+/// attribute with the path to the module annotated with `define`:
+///
+/// ```ignore
+/// #[generic_tests::instantiate(crate::suites::codec, <MyCodec>)]
+/// mod codec_tests {}
+/// ```
+///
+/// The generic test functions and the submodules containing them are made
+/// visible in the crate. The suite module itself, and any items referred to
+/// in the test function signatures, such as argument and return types,
+/// need to be visible at the instantiation as well: the instantiated
+/// functions find them through a glob import of the suite module, which
+/// does not bring in private imports. A type imported into the suite with
+/// `use std::borrow::Cow;` therefore needs `pub use` instead, or
+/// a full path in the signatures.
+///
+/// A suite defined with the `export` parameter, as in
+/// `#[generic_tests::define(export)]`, can be instantiated in other crates
+/// as well. The generic test functions and the submodules containing them
+/// are made public; the suite module itself, and any items referred to in
+/// the test function signatures, need to be public for the instantiations
+/// to use them. Paths starting with `crate` in the test function
+/// signatures and the `case()` values refer to the defining crate
+/// in the instantiations. Note that an exported suite should not be gated
+/// by `cfg(test)`, which only applies when the defining crate is tested.
+/// The crate instantiating the suite needs to depend on `generic-tests`.
+/// The hidden macro through which a suite is exported is named after
+/// the suite module and its test declarations, so two exported suites
+/// in one crate cannot have both the same name and the same tests.
+///
+/// ```ignore
+/// // In the crate defining the `Codec` trait
+/// pub mod conformance {
+///     #[generic_tests::define(export)]
+///     pub mod codec {
+///         pub use crate::Codec;
+///
+///         #[test]
+///         fn encodes_empty<C: Codec>() {
+///             assert!(C::encode("").is_empty());
+///         }
+///     }
+/// }
+///
+/// // In a crate implementing the trait
+/// #[cfg(test)]
+/// #[generic_tests::instantiate(codec_crate::conformance::codec, <MyCodec>)]
+/// mod codec_tests {}
+/// ```
+///
+/// # Parameters shared by all tests
+///
+/// When all tests in the module are parameterized in the same way, the
+/// generic parameters can be declared once with the `params()` option,
+/// which may also have a `where` clause. The declared parameters are
+/// added to each test function before any parameters of its own,
+/// and the instantiation arguments are given in that order.
+/// As the test functions need no generic parameters of their own,
+/// all of them are instantiated. A test that does not use the parameters
+/// can be marked with `#[generic_test(no_params)]` to be left in the module
+/// as an ordinary test.
+///
+/// ```
+/// #[generic_tests::define(params(<T: Buf + From<&'static [u8]>, const N: usize>))]
+/// mod tests {
+///     use bytes::{Buf, Bytes};
+///
+///     #[test]
+///     fn advance() {
+///         let mut buf = T::from(b"Hello");
+///         buf.advance(N);
+///         assert_eq!(buf.remaining(), 5 - N);
+///     }
+///
+///     #[test]
+///     #[generic_test(no_params)]
+///     fn sanity() {
+///         assert_eq!(Bytes::from_static(b"Hello").len(), 5);
+///     }
+///
+///     #[instantiate_tests(<Bytes, 2>)]
+///     mod shared {}
+///
+///     #[instantiate_tests(<&'static [u8], 5>)]
+///     mod slice {}
+/// }
+/// # fn main() {}
+/// ```
+///
+/// # Setup and teardown
+///
+/// A function in the module marked with `#[generic_setup]` is called
+/// before each instantiated test, and a function marked with
+/// `#[generic_teardown]` is called after it, also when the test panics.
+/// The functions take no parameters and are generic over the same
+/// parameters as the tests; with named instantiation arguments, they can
+/// use a subset of the parameters. A submodule of the suite can have
+/// its own setup and teardown functions, which are called for its tests
+/// after the setup and before the teardown of the enclosing modules.
+/// Async setup and teardown functions are awaited in async tests,
+/// and cannot apply to non-async tests.
+///
+/// ```
+/// #[generic_tests::define]
+/// mod tests {
+///     use std::cell::RefCell;
+///
+///     thread_local! {
+///         static LOG: RefCell<Vec<String>> = RefCell::new(Vec::new());
+///     }
+///
+///     #[generic_setup]
+///     fn setup<T: Default + ToString>() {
+///         LOG.with(|log| log.borrow_mut().push(T::default().to_string()));
+///     }
+///
+///     #[generic_teardown]
+///     fn teardown<T>() {
+///         LOG.with(|log| log.borrow_mut().clear());
+///     }
+///
+///     #[test]
+///     fn logged<T: Default + ToString>() {
+///         LOG.with(|log| assert_eq!(*log.borrow(), [T::default().to_string()]));
+///     }
+///
+///     #[instantiate_tests(<i32>)]
+///     mod int {}
+/// }
+/// # fn main() {}
+/// ```
+///
+/// # Fixtures
+///
+/// An argument of a test function can be marked with `#[fixture]` to
+/// have its value supplied by the instantiation with the `fixture = expr`
+/// option. The argument is omitted from the signature of the instantiated
+/// test function, and the expression is evaluated for each call of the
+/// test in the scope of the instantiation module, so it can refer to
+/// items defined in that module as well as to the names visible where
+/// the instantiation arguments are resolved.
+/// An instantiation that selects tests with fixture arguments must
+/// provide the fixture, and the fixture is only allowed when it is used.
+///
+/// ```
+/// #[generic_tests::define]
+/// mod tests {
+///     use std::collections::VecDeque;
+///     use std::io::{Read, Write};
+///
+///     #[test]
+///     fn write_then_read<S: Read + Write>(#[fixture] mut stream: S) {
+///         stream.write_all(b"Hello").unwrap();
+///         let mut buf = [0; 5];
+///         stream.read_exact(&mut buf).unwrap();
+///         assert_eq!(&buf, b"Hello");
+///     }
+///
+///     #[instantiate_tests(<VecDeque<u8>>, fixture = VecDeque::new())]
+///     mod deque {}
+/// }
+/// # fn main() {}
+/// ```
+///
+/// # Cases
+///
+/// A generic test function with arguments can be given a table of argument
+/// values with `case()` entries in the `generic_test` attribute. Each
+/// instantiation then has a test function for each case, named after the
+/// generic function with a `_case_` suffix and the number of the case
+/// starting from 1, which calls the generic function with the values of
+/// the case. The values are given for the arguments that are not fixtures.
+/// A test function in the same module that has one of the generated names
+/// is reported as an error.
+///
+/// ```
+/// #[generic_tests::define]
+/// mod tests {
+///     use std::fmt::Debug;
+///     use std::str::FromStr;
+///
+///     #[test]
+///     #[generic_test(case(1, "1"), case(2, "+2"))]
+///     fn parses<T>(expected: u8, input: &str)
+///     where
+///         T: FromStr + From<u8> + PartialEq + Debug,
+///         T::Err: Debug,
+///     {
+///         assert_eq!(input.parse::<T>().unwrap(), T::from(expected));
+///     }
+///
+///     // Instantiates `parses_case_1` and `parses_case_2`
+///     #[instantiate_tests(<u32>)]
+///     mod int {}
+/// }
+/// # fn main() {}
+/// ```
+///
+/// # Modules in separate files
+///
+/// The macro can be applied to a module declared without a body, as in
+/// `mod suite;`. The macro reads the module's source file, located in the
+/// same way as rustc would locate it for a module declared at the top
+/// level of the file invoking the macro, including the `path` attribute,
+/// and processes the module as if it were defined inline. Out-of-line
+/// modules declared in the file are also located relative to it.
+///
+/// The invoking file is recognized as a crate root, which has the modules
+/// it declares located in its own directory, if it is named `lib.rs` or
+/// `main.rs`, or if it is in the `tests`, `examples`, `benches`, or
+/// `src/bin` directory of the package and is named after the crate being
+/// compiled. A crate root at another path, such as one configured in
+/// `Cargo.toml`, is treated as a non-root module file.
+///
+/// The file is included into the build, so that changes to it cause
+/// the tests to be rebuilt. Note that out-of-line modules in the input of
+/// attribute macros are currently only supported with a nightly toolchain
+/// and the `proc_macro_hygiene` feature. Loading the module files also
+/// needs the `file-modules` feature of this crate, which requires
+/// Rust 1.88 or later to build.
+///
+/// ```toml
+/// [dev-dependencies]
+/// generic-tests = { version = "0.1", features = ["file-modules"] }
+/// ```
+///
+/// ```ignore
+/// #![feature(proc_macro_hygiene)]
+///
+/// #[generic_tests::define]
+/// mod suite;
+/// ```
+///
 /// # Const generics
 ///
 /// Since Rust 1.51, const generic parameters can be used to parameterize test
@@ -194,7 +885,119 @@ use syn::{meta, ItemMod};
 pub fn define(args: TokenStream, item: TokenStream) -> TokenStream {
     let mut opts = ParsedMacroOpts::default();
     let opts_parser = meta::parser(|meta| opts.parse(meta));
+    let args_tokens = args.clone().into();
     parse_macro_input!(args with opts_parser);
     let ast = parse_macro_input!(item as ItemMod);
-    expand::expand(&opts.into_effective(), ast).into()
+    expand::expand(&opts.into_effective(), args_tokens, ast).into()
+}
+
+/// Instantiates a suite defined elsewhere in the crate.
+///
+/// The first argument of the attribute is the path to a module annotated
+/// with [`define`], followed by the instantiation arguments and options
+/// in the same form as for the `instantiate_tests` attribute.
+/// The module annotated with this attribute is populated with the
+/// instantiated tests, as it would be if it were annotated with
+/// `instantiate_tests` inside the suite module.
+///
+/// The suite path and the instantiation arguments are resolved in the scope
+/// enclosing the annotated module. The generic test functions of the suite
+/// are made visible in the crate; any other items of the suite module that
+/// are referred to in the test function signatures need to be visible
+/// at the instantiation as well. Suites defined with
+/// `#[generic_tests::define(export)]` can also be instantiated in
+/// other crates.
+///
+/// ```
+/// mod suites {
+///     #[generic_tests::define]
+///     pub mod codec {
+///         pub trait Codec {
+///             fn encode(s: &str) -> Vec<u8>;
+///         }
+///
+///         #[test]
+///         fn encodes_empty<C: Codec>() {
+///             assert!(C::encode("").is_empty());
+///         }
+///     }
+/// }
+///
+/// struct Utf8;
+///
+/// impl suites::codec::Codec for Utf8 {
+///     fn encode(s: &str) -> Vec<u8> {
+///         s.as_bytes().to_vec()
+///     }
+/// }
+///
+/// #[generic_tests::instantiate(suites::codec, <Utf8>)]
+/// mod utf8_tests {}
+/// # fn main() {}
+/// ```
+#[proc_macro_attribute]
+pub fn instantiate(args: TokenStream, item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as ItemMod);
+    external::expand_attr(args.into(), item).into()
+}
+
+/// Instantiates a suite for the annotated type.
+///
+/// The attribute can be put on a struct, enum, union, or type alias
+/// to emit a sibling module, gated by `cfg(test)`, instantiating the suite
+/// module at the given path as the [`instantiate`] attribute does.
+/// The module is named after the snake-cased type and the suite, e.g.
+/// `ring_buf_buffer` for the example below. The type is the only
+/// instantiation argument, unless the arguments are given explicitly after
+/// the path; options of the instantiation can follow either way.
+///
+/// ```
+/// #[cfg(test)]
+/// mod suites {
+///     #[generic_tests::define]
+///     pub mod buffer {
+///         #[test]
+///         fn default_is_empty<B: Default + AsRef<[u8]>>() {
+///             assert!(B::default().as_ref().is_empty());
+///         }
+///     }
+/// }
+///
+/// #[generic_tests::test_with(crate::suites::buffer)]
+/// #[derive(Default)]
+/// struct RingBuf {
+///     data: Vec<u8>,
+/// }
+///
+/// impl AsRef<[u8]> for RingBuf {
+///     fn as_ref(&self) -> &[u8] {
+///         &self.data
+///     }
+/// }
+///
+/// #[generic_tests::test_with(crate::suites::buffer, <FixedBuf<16>>)]
+/// #[derive(Default)]
+/// struct FixedBuf<const N: usize> {
+///     data: Vec<u8>,
+/// }
+/// #
+/// # impl<const N: usize> AsRef<[u8]> for FixedBuf<N> {
+/// #     fn as_ref(&self) -> &[u8] {
+/// #         &self.data
+/// #     }
+/// # }
+/// # fn main() {}
+/// ```
+#[proc_macro_attribute]
+pub fn test_with(args: TokenStream, item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as Item);
+    external::expand_test_with(args.into(), item).into()
+}
+
+// Invoked by the companion macro of a suite, with the test declarations
+// of the suite and the module to instantiate them in
+#[doc(hidden)]
+#[proc_macro]
+pub fn __instantiate(input: TokenStream) -> TokenStream {
+    external::expand_instantiation(input.into()).into()
 }
@@ -0,0 +1,231 @@
+use crate::error::ErrorRecord;
+
+use syn::ext::IdentExt;
+use syn::token::Brace;
+use syn::visit_mut::{self, VisitMut};
+use syn::{parse_quote, Attribute, Error, Expr, ExprLit, Ident, ItemMod, Lit, Meta};
+
+use std::env;
+use std::fs;
+use std::mem;
+use std::path::{Path, PathBuf};
+
+// The source file of the macro invocation. Locating it needs
+// the source location API of `proc_macro` stabilized in Rust 1.88,
+// so it is only used with the `file-modules` feature.
+#[cfg(feature = "file-modules")]
+fn invoking_file(item: &ItemMod) -> syn::Result<PathBuf> {
+    proc_macro2::Span::call_site()
+        .unwrap()
+        .local_file()
+        .and_then(|path| {
+            // A relative path is relative to the working directory of rustc
+            let cwd = env::current_dir().ok()?;
+            Some(cwd.join(path))
+        })
+        .ok_or_else(|| {
+            Error::new_spanned(item, "cannot locate the source file declaring the module")
+        })
+}
+
+#[cfg(not(feature = "file-modules"))]
+fn invoking_file(item: &ItemMod) -> syn::Result<PathBuf> {
+    Err(Error::new_spanned(
+        item,
+        "loading a module from its file requires the `file-modules` feature of `generic-tests`",
+    ))
+}
+
+// Loads the content of an out-of-line module from its source file,
+// making the module inline. The file is located following the rules
+// that rustc applies to a module declared at the top level of the file
+// invoking the macro.
+pub fn load(item: &mut ItemMod) -> syn::Result<()> {
+    debug_assert!(item.content.is_none());
+
+    let invoking_file = invoking_file(item)?;
+    let invoking_dir = invoking_file.parent().unwrap_or(Path::new(""));
+    let (path, nested_dir) = match take_path_attr(&mut item.attrs)? {
+        Some(path) => {
+            // A file given with the path attribute owns its directory
+            let path = invoking_dir.join(path);
+            let dir = path.parent().unwrap_or(Path::new("")).to_owned();
+            (path, dir)
+        }
+        None => {
+            let dir = if is_mod_rs(&invoking_file) {
+                invoking_dir.to_owned()
+            } else {
+                invoking_dir.join(invoking_file.file_stem().unwrap_or_default())
+            };
+            let path = find_module_file(&dir, &item.ident)
+                .map_err(|msg| Error::new_spanned(&*item, msg))?;
+            let nested_dir = if path.ends_with("mod.rs") {
+                path.parent().unwrap().to_owned()
+            } else {
+                dir.join(item.ident.unraw().to_string())
+            };
+            (path, nested_dir)
+        }
+    };
+
+    let source = fs::read_to_string(&path).map_err(|e| {
+        Error::new_spanned(
+            &*item,
+            format!("cannot read module file `{}`: {}", path.display(), e),
+        )
+    })?;
+    let file = syn::parse_file(&source).map_err(|e| {
+        Error::new_spanned(
+            &*item,
+            format!("cannot parse module file `{}`: {}", path.display(), e),
+        )
+    })?;
+
+    let mut items = file.items;
+    let mut nested_paths = NestedModulePaths {
+        dir: nested_dir,
+        errors: Default::default(),
+    };
+    for item in &mut items {
+        nested_paths.visit_item_mut(item);
+    }
+    nested_paths.errors.check()?;
+
+    // Make the build depend on the file
+    let path_str = path.to_string_lossy();
+    items.insert(
+        0,
+        parse_quote! {
+            const _: &[u8] = include_bytes!(#path_str);
+        },
+    );
+
+    item.attrs.extend(file.attrs);
+    item.content = Some((Brace::default(), items));
+    item.semi = None;
+    Ok(())
+}
+
+// Checks if the file is a crate root or a `mod.rs` file, which has
+// the modules it declares located in its own directory.
+fn is_mod_rs(file: &Path) -> bool {
+    if let Some("mod.rs" | "lib.rs" | "main.rs" | "build.rs") =
+        file.file_name().and_then(|name| name.to_str())
+    {
+        return true;
+    }
+    // Cargo target roots in the conventional directories. Other files
+    // there can be modules of the targets, e.g. `tests/common.rs`,
+    // so the file must also be named after the crate being compiled.
+    let (manifest_dir, crate_name) = match (
+        env::var_os("CARGO_MANIFEST_DIR"),
+        env::var("CARGO_CRATE_NAME"),
+    ) {
+        (Some(dir), Ok(name)) => (PathBuf::from(dir), name),
+        _ => return false,
+    };
+    let is_crate_file = file
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .is_some_and(|stem| stem.replace('-', "_") == crate_name);
+    let parent = file.parent().unwrap_or(Path::new(""));
+    is_crate_file
+        && ["tests", "examples", "benches", "src/bin"]
+            .iter()
+            .any(|dir| parent == manifest_dir.join(dir))
+}
+
+fn find_module_file(dir: &Path, ident: &Ident) -> Result<PathBuf, String> {
+    let name = ident.unraw().to_string();
+    let file_path = dir.join(format!("{}.rs", name));
+    let mod_rs_path = dir.join(&name).join("mod.rs");
+    match (file_path.is_file(), mod_rs_path.is_file()) {
+        (true, false) => Ok(file_path),
+        (false, true) => Ok(mod_rs_path),
+        (true, true) => Err(format!(
+            "file for module `{}` found at both `{}` and `{}`",
+            name,
+            file_path.display(),
+            mod_rs_path.display(),
+        )),
+        (false, false) => Err(format!(
+            "file not found for module `{}`; expected `{}` or `{}`",
+            name,
+            file_path.display(),
+            mod_rs_path.display(),
+        )),
+    }
+}
+
+fn take_path_attr(attrs: &mut Vec<Attribute>) -> syn::Result<Option<String>> {
+    let pos = match attrs.iter().position(|attr| attr.path().is_ident("path")) {
+        Some(pos) => pos,
+        None => return Ok(None),
+    };
+    let attr = attrs.remove(pos);
+    match attr.meta {
+        Meta::NameValue(nv) => match nv.value {
+            Expr::Lit(ExprLit {
+                lit: Lit::Str(lit), ..
+            }) => Ok(Some(lit.value())),
+            value => Err(Error::new_spanned(value, "expected a string literal")),
+        },
+        meta => Err(Error::new_spanned(
+            meta,
+            "expected the attribute in the form `#[path = \"...\"]`",
+        )),
+    }
+}
+
+// Sets absolute paths on the out-of-line modules declared in the loaded file,
+// as they would otherwise be looked up relative to the file invoking the macro.
+struct NestedModulePaths {
+    dir: PathBuf,
+    errors: ErrorRecord,
+}
+
+impl VisitMut for NestedModulePaths {
+    fn visit_item_mod_mut(&mut self, item: &mut ItemMod) {
+        // Instantiation modules declared without a body are made inline
+        if item.content.is_none()
+            && item
+                .attrs
+                .iter()
+                .any(|attr| attr.path().is_ident("instantiate_tests"))
+        {
+            return;
+        }
+        let path_attr = match take_path_attr(&mut item.attrs) {
+            Ok(path) => path,
+            Err(e) => {
+                self.errors.add_error(e);
+                return;
+            }
+        };
+        if item.content.is_some() {
+            let dir = match path_attr {
+                Some(path) => self.dir.join(path),
+                None => self.dir.join(item.ident.unraw().to_string()),
+            };
+            let outer_dir = mem::replace(&mut self.dir, dir);
+            visit_mut::visit_item_mod_mut(self, item);
+            self.dir = outer_dir;
+            return;
+        }
+        let path = match path_attr {
+            Some(path) => self.dir.join(path),
+            None => match find_module_file(&self.dir, &item.ident) {
+                Ok(path) => path,
+                // The module may be configured out and have no file
+                Err(_) if item.attrs.iter().any(|attr| attr.path().is_ident("cfg")) => return,
+                Err(msg) => {
+                    self.errors.add_error(Error::new_spanned(&*item, msg));
+                    return;
+                }
+            },
+        };
+        let path_str = path.to_string_lossy();
+        item.attrs.push(parse_quote! { #[path = #path_str] });
+    }
+}
@@ -0,0 +1,71 @@
+use proc_macro2::{Delimiter, Span, TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::{Error, Ident};
+
+// Derives a snake_case module name from the tokens of instantiation
+// arguments, e.g. `vec_u8` from `Vec<u8>` or `cow_static_str` from
+// `Cow<'static, str>`.
+pub fn derive_mod_name(args: &impl ToTokens) -> syn::Result<Ident> {
+    let mut words = Vec::new();
+    collect_words(args.to_token_stream(), &mut words);
+    if words.is_empty() {
+        return Err(Error::new_spanned(
+            args,
+            "cannot derive a module name from the instantiation arguments",
+        ));
+    }
+    let mut name = words.join("_");
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, '_');
+    }
+    if syn::parse_str::<Ident>(&name).is_err() {
+        // The name is a keyword
+        name.push('_');
+    }
+    Ok(Ident::new(&name, Span::call_site()))
+}
+
+fn collect_words(tokens: TokenStream, words: &mut Vec<String>) {
+    for tt in tokens {
+        match tt {
+            TokenTree::Group(group) => {
+                if group.delimiter() == Delimiter::Parenthesis && group.stream().is_empty() {
+                    words.push("unit".into());
+                } else {
+                    collect_words(group.stream(), words);
+                }
+            }
+            TokenTree::Ident(ident) => {
+                let s = ident.to_string();
+                words.push(to_snake_case(s.strip_prefix("r#").unwrap_or(&s)));
+            }
+            TokenTree::Literal(lit) => {
+                let s = lit.to_string();
+                words.extend(
+                    s.split(|c: char| !c.is_ascii_alphanumeric())
+                        .filter(|w| !w.is_empty())
+                        .map(str::to_ascii_lowercase),
+                );
+            }
+            TokenTree::Punct(_) => {}
+        }
+    }
+}
+
+fn to_snake_case(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 4);
+    let mut prev_lower = false;
+    for c in s.chars() {
+        if c.is_uppercase() {
+            if prev_lower {
+                out.push('_');
+            }
+            out.extend(c.to_lowercase());
+            prev_lower = false;
+        } else {
+            out.push(c);
+            prev_lower = c.is_lowercase() || c.is_ascii_digit();
+        }
+    }
+    out
+}
@@ -1,29 +1,82 @@
+use crate::error::ErrorRecord;
+use crate::extract::TestFn;
+use crate::instantiation::RootInstantiation;
+
 use proc_macro2::Span;
 use syn::meta::ParseNestedMeta;
 use syn::parse::{Parse, ParseBuffer};
-use syn::{parenthesized, Token};
-use syn::{Attribute, Error, Ident, Meta, Path};
+use syn::punctuated::Punctuated;
+use syn::{parenthesized, parse_quote, Token};
+use syn::{Attribute, Error, Expr, Generics, Ident, LitInt, LitStr, Meta, Path};
 
 use std::collections::HashSet;
 
 const DEFAULT_TEST_ATTRS: &[&str] = &["test", "ignore", "should_panic", "bench"];
 const DEFAULT_COPIED_ATTRS: &[&str] = &["cfg"];
+const DEFAULT_MATRIX_LIMIT: usize = 64;
 
 pub struct MacroOpts {
     inst_attrs: HashSet<Path>,
     copy_attrs: HashSet<Path>,
+    pub matrix_limit: usize,
+    pub instantiations: Vec<RootInstantiation>,
+    // Generic parameters added to every test function
+    pub params: Option<Generics>,
+    // Whether the suite can be instantiated in other crates
+    pub export: bool,
+    // Paths of the suites whose tests are included in this suite
+    pub includes: Vec<Path>,
 }
 
 #[derive(Default)]
 pub struct ParsedMacroOpts {
     inst_attrs: Option<HashSet<Path>>,
     copy_attrs: Option<HashSet<Path>>,
+    matrix_limit: Option<usize>,
+    instantiations: Vec<RootInstantiation>,
+    params: Option<Generics>,
+    export: bool,
+    includes: Vec<Path>,
+}
+
+// Options given after the arguments in an `instantiate_tests` attribute
+#[derive(Default)]
+pub struct InstOpts {
+    filter: Option<TestFilter>,
+    // Test attributes added to all test functions,
+    // replacing any attributes with the same path
+    extra_attrs: Vec<Attribute>,
+    // Test attributes added to the named test functions,
+    // replacing any attributes with the same path
+    attr_overrides: Vec<(Ident, Attribute)>,
+    // Predicates of `cfg` attributes gating the instantiation module
+    mod_cfgs: Vec<Meta>,
+    // Predicates of `cfg` attributes added to the named test functions
+    test_cfgs: Vec<(Ident, Meta)>,
+    // Expression evaluated for the fixture arguments of the tests
+    fixture: Option<Expr>,
+}
+
+// Selection of the generic test functions to instantiate
+enum TestFilter {
+    Only(Vec<Ident>),
+    Except(Vec<Ident>),
 }
 
 #[derive(Default)]
 pub struct TestFnOpts {
     inst_attrs: Option<HashSet<Path>>,
     copy_attrs: Option<HashSet<Path>>,
+    cases: Vec<TestCase>,
+    // The `no_params` keyword, if the test opts out of the suite parameters
+    no_params: Option<Ident>,
+}
+
+// Argument values given with `case()` in the `generic_test` attribute
+pub struct TestCase {
+    // The `case` keyword, used for error reporting
+    pub ident: Ident,
+    pub values: Punctuated<Expr, Token![,]>,
 }
 
 pub fn is_test_attr(attr: &Attribute, macro_opts: &MacroOpts, fn_opts: &TestFnOpts) -> bool {
@@ -62,6 +115,11 @@ impl Default for MacroOpts {
         MacroOpts {
             inst_attrs: set_from_attr_names(DEFAULT_TEST_ATTRS),
             copy_attrs: set_from_attr_names(DEFAULT_COPIED_ATTRS),
+            matrix_limit: DEFAULT_MATRIX_LIMIT,
+            instantiations: Vec::new(),
+            params: None,
+            export: false,
+            includes: Vec::new(),
         }
     }
 }
@@ -72,6 +130,32 @@ impl ParsedMacroOpts {
             populate_from_attr_list(meta.input, self.inst_attrs.get_or_insert(HashSet::new()))?;
         } else if meta.path.is_ident("copy_attrs") {
             populate_from_attr_list(meta.input, self.copy_attrs.get_or_insert(HashSet::new()))?;
+        } else if meta.path.is_ident("matrix_limit") {
+            let limit: LitInt = meta.value()?.parse()?;
+            self.matrix_limit = Some(limit.base10_parse()?);
+        } else if meta.path.is_ident("instantiate") {
+            let content;
+            parenthesized!(content in meta.input);
+            let list = content.parse_terminated(RootInstantiation::parse, Token![,])?;
+            self.instantiations.extend(list);
+        } else if meta.path.is_ident("params") {
+            if self.params.is_some() {
+                return Err(meta.error("duplicate `params()`"));
+            }
+            let content;
+            parenthesized!(content in meta.input);
+            let mut generics: Generics = content.parse()?;
+            if content.peek(Token![where]) {
+                generics.where_clause = Some(content.parse()?);
+            }
+            self.params = Some(generics);
+        } else if meta.path.is_ident("export") {
+            self.export = true;
+        } else if meta.path.is_ident("include") {
+            let content;
+            parenthesized!(content in meta.input);
+            let paths = content.parse_terminated(Path::parse_mod_style, Token![,])?;
+            self.includes.extend(paths);
         } else {
             return Err(meta.error("unsupported attribute"));
         }
@@ -86,14 +170,182 @@ impl ParsedMacroOpts {
             copy_attrs: self
                 .copy_attrs
                 .unwrap_or_else(|| set_from_attr_names(DEFAULT_COPIED_ATTRS)),
+            matrix_limit: self.matrix_limit.unwrap_or(DEFAULT_MATRIX_LIMIT),
+            instantiations: self.instantiations,
+            params: self.params,
+            export: self.export,
+            includes: self.includes,
+        }
+    }
+}
+
+impl InstOpts {
+    pub fn parse(&mut self, meta: ParseNestedMeta) -> syn::Result<()> {
+        if meta.path.is_ident("only") || meta.path.is_ident("except") {
+            self.parse_filter(meta)
+        } else if meta.path.is_ident("attrs") {
+            let content;
+            parenthesized!(content in meta.input);
+            let list = content.parse_terminated(Meta::parse, Token![,])?;
+            self.extra_attrs
+                .extend(list.into_iter().map(|meta| -> Attribute {
+                    parse_quote! { #[#meta] }
+                }));
+            Ok(())
+        } else if meta.path.is_ident("ignore") {
+            meta.parse_nested_meta(|meta| {
+                let test = meta.path.require_ident()?.clone();
+                let attr = if meta.input.peek(Token![=]) {
+                    let reason: LitStr = meta.value()?.parse()?;
+                    parse_quote! { #[ignore = #reason] }
+                } else {
+                    parse_quote! { #[ignore] }
+                };
+                self.attr_overrides.push((test, attr));
+                Ok(())
+            })
+        } else if meta.path.is_ident("should_panic") {
+            meta.parse_nested_meta(|meta| {
+                let test = meta.path.require_ident()?.clone();
+                let attr = if meta.input.peek(Token![=]) {
+                    let expected: LitStr = meta.value()?.parse()?;
+                    parse_quote! { #[should_panic(expected = #expected)] }
+                } else {
+                    parse_quote! { #[should_panic] }
+                };
+                self.attr_overrides.push((test, attr));
+                Ok(())
+            })
+        } else if meta.path.is_ident("cfg") {
+            let content;
+            parenthesized!(content in meta.input);
+            self.mod_cfgs.push(content.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("fixture") {
+            if self.fixture.is_some() {
+                return Err(meta.error("duplicate `fixture`"));
+            }
+            self.fixture = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("test_cfg") {
+            meta.parse_nested_meta(|meta| {
+                let test = meta.path.require_ident()?.clone();
+                let content;
+                parenthesized!(content in meta.input);
+                self.test_cfgs.push((test, content.parse()?));
+                Ok(())
+            })
+        } else {
+            Err(meta.error("unsupported instantiation option"))
+        }
+    }
+
+    fn parse_filter(&mut self, meta: ParseNestedMeta) -> syn::Result<()> {
+        if self.filter.is_some() {
+            return Err(meta.error("only one of `only()` or `except()` can be given"));
+        }
+        let make_filter = if meta.path.is_ident("only") {
+            TestFilter::Only
+        } else {
+            TestFilter::Except
+        };
+        let content;
+        parenthesized!(content in meta.input);
+        let names = content.parse_terminated(Ident::parse, Token![,])?;
+        self.filter = Some(make_filter(names.into_iter().collect()));
+        Ok(())
+    }
+
+    // Checks if any options other than `cfg()` are given
+    pub fn has_test_options(&self) -> bool {
+        self.filter.is_some()
+            || !self.extra_attrs.is_empty()
+            || !self.attr_overrides.is_empty()
+            || !self.test_cfgs.is_empty()
+            || self.fixture.is_some()
+    }
+
+    pub fn fixture(&self) -> Option<&Expr> {
+        self.fixture.as_ref()
+    }
+
+    pub fn selects(&self, test: &TestFn) -> bool {
+        match &self.filter {
+            None => true,
+            Some(TestFilter::Only(names)) => names.contains(&test.ident),
+            Some(TestFilter::Except(names)) => !names.contains(&test.ident),
+        }
+    }
+
+    // Returns the test attributes for the instantiated function
+    pub fn test_attrs(&self, test: &TestFn) -> Vec<Attribute> {
+        let test_overrides = self
+            .attr_overrides
+            .iter()
+            .filter(|(name, _)| *name == test.ident)
+            .map(|(_, attr)| attr)
+            .collect::<Vec<_>>();
+        let overrides = self
+            .extra_attrs
+            .iter()
+            .filter(|attr| {
+                !test_overrides
+                    .iter()
+                    .any(|o| o.meta.path() == attr.meta.path())
+            })
+            .chain(test_overrides.iter().copied())
+            .collect::<Vec<_>>();
+        let mut attrs = test
+            .test_attrs
+            .iter()
+            .filter(|attr| !overrides.iter().any(|o| o.meta.path() == attr.meta.path()))
+            .cloned()
+            .collect::<Vec<_>>();
+        attrs.extend(overrides.into_iter().cloned());
+        for (_, predicate) in self
+            .test_cfgs
+            .iter()
+            .filter(|(name, _)| *name == test.ident)
+        {
+            attrs.push(parse_quote! { #[cfg(#predicate)] });
         }
+        attrs
+    }
+
+    // Returns the attributes for the instantiation module
+    pub fn mod_attrs(&self) -> Vec<Attribute> {
+        self.mod_cfgs
+            .iter()
+            .map(|predicate| parse_quote! { #[cfg(#predicate)] })
+            .collect()
+    }
+
+    // Checks that each test function named in the options matches
+    // a generic test
+    pub fn check_test_names(&self, tests: &[TestFn]) -> syn::Result<()> {
+        let filter_names = match &self.filter {
+            None => &[][..],
+            Some(TestFilter::Only(names) | TestFilter::Except(names)) => names,
+        };
+        let override_names = self.attr_overrides.iter().map(|(name, _)| name);
+        let cfg_names = self.test_cfgs.iter().map(|(name, _)| name);
+        let mut errors = ErrorRecord::default();
+        for name in filter_names.iter().chain(override_names).chain(cfg_names) {
+            if !tests.iter().any(|test| test.ident == *name) {
+                errors.add_error(Error::new_spanned(
+                    name,
+                    format!("no generic test function named `{}`", name),
+                ));
+            }
+        }
+        errors.check()
     }
 }
 
 impl TestFnOpts {
     pub fn apply_attr(&mut self, attr_meta: Meta) -> syn::Result<()> {
         const ERROR_MSG: &str = "unexpected attribute input; \
-                use `attrs()`, `copy_attrs()`";
+                use `attrs()`, `copy_attrs()`, `case()`, `no_params`";
 
         match attr_meta {
             Meta::List(list) => {
@@ -108,6 +360,15 @@ impl TestFnOpts {
                             meta.input,
                             self.copy_attrs.get_or_insert(HashSet::new()),
                         )?;
+                    } else if meta.path.is_ident("case") {
+                        let content;
+                        parenthesized!(content in meta.input);
+                        self.cases.push(TestCase {
+                            ident: meta.path.require_ident()?.clone(),
+                            values: content.parse_terminated(Expr::parse, Token![,])?,
+                        });
+                    } else if meta.path.is_ident("no_params") {
+                        self.no_params = Some(meta.path.require_ident()?.clone());
                     } else {
                         return Err(meta.error(ERROR_MSG));
                     }
@@ -117,11 +378,24 @@ impl TestFnOpts {
             Meta::Path(path) => {
                 return Err(Error::new_spanned(
                     path,
-                    "attribute must have arguments; use `attrs()`, `copy_attrs()`",
+                    "attribute must have arguments; \
+                    use `attrs()`, `copy_attrs()`, `case()`, `no_params`",
                 ))
             }
             Meta::NameValue(nv) => return Err(Error::new_spanned(nv, ERROR_MSG)),
         };
         Ok(())
     }
+
+    pub fn cases(&self) -> &[TestCase] {
+        &self.cases
+    }
+
+    pub fn no_params(&self) -> Option<&Ident> {
+        self.no_params.as_ref()
+    }
+
+    pub fn into_cases(self) -> Vec<TestCase> {
+        self.cases
+    }
 }
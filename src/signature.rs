@@ -1,14 +1,17 @@
 use crate::error::ErrorRecord;
 
 use proc_macro2::Span;
+use quote::format_ident;
 use syn::punctuated::Punctuated;
 use syn::visit::{self, Visit};
 use syn::visit_mut::{self, VisitMut};
 use syn::{parse_quote, Token};
 use syn::{
-    Attribute, BoundLifetimes, ConstParam, Error, FnArg, GenericParam, Generics, Ident, ItemFn,
-    Lifetime, ParenthesizedGenericArguments, Pat, PatIdent, Path, PathSegment, ReturnType,
-    Signature, TraitBound, Type, TypeBareFn, TypeParam, TypePath, TypeReference, WherePredicate,
+    Attribute, BoundLifetimes, ConstParam, Error, ExprPath, FnArg, GenericArgument, GenericParam,
+    Generics, Ident, ItemFn, Lifetime, ParenthesizedGenericArguments, Pat, PatIdent, Path,
+    PathArguments, PathSegment, PredicateLifetime, PredicateType, ReturnType, Signature,
+    TraitBound, TraitBoundModifier, Type, TypeBareFn, TypeParam, TypeParamBound, TypePath,
+    TypeReference, WhereClause, WherePredicate,
 };
 
 use std::collections::HashSet;
@@ -18,6 +21,37 @@ pub struct TestFnSignature {
     pub input: TestInputSignature,
     pub output: TestReturnSignature,
     pub lifetime_params: Punctuated<Lifetime, Token![,]>,
+    // Outlives bounds between the lifetimes in the signature
+    pub lifetime_bounds: Vec<PredicateLifetime>,
+    pub generic_params: Vec<TestGenericParam>,
+}
+
+// A type or const parameter of the test function
+#[derive(Clone)]
+pub struct TestGenericParam {
+    pub ident: Ident,
+    pub kind: TestGenericParamKind,
+    // Whether the parameter is used in the function signature
+    pub in_signature: bool,
+}
+
+#[derive(Clone)]
+pub enum TestGenericParamKind {
+    // The trait bounds are used to qualify associated types of the parameter
+    Type { trait_bounds: Vec<Path> },
+    Const { ty: Type },
+}
+
+impl TestGenericParamKind {
+    // Checks if the parameters are of the same kind, and const parameters
+    // are of the same type
+    pub fn matches(&self, other: &TestGenericParamKind) -> bool {
+        match (self, other) {
+            (Self::Type { .. }, Self::Type { .. }) => true,
+            (Self::Const { ty }, Self::Const { ty: other_ty }) => ty == other_ty,
+            _ => false,
+        }
+    }
 }
 
 pub struct TestSignatureItem {
@@ -30,7 +64,11 @@ pub struct TestSignatureItem {
 
 pub struct TestInputSignature {
     pub item: TestSignatureItem,
+    // The arguments other than fixtures
     pub args: Vec<TestFnArg>,
+    // Positions of the arguments marked with `#[fixture]`,
+    // which are filled in by the instantiation
+    pub fixture_positions: Vec<usize>,
 }
 
 pub struct TestFnArg {
@@ -78,6 +116,8 @@ impl TestFnArg {
 impl TestFnSignature {
     pub fn try_build(item: &ItemFn) -> syn::Result<Self> {
         validate(&item.sig)?;
+        let mut generic_params = collect_generic_params(&item.sig.generics);
+        collect_generic_param_uses(&item.sig, &mut generic_params)?;
         let input = TestInputSignature::try_build(&item.sig.inputs)?;
         let (output, lifetimes) = match &item.sig.output {
             ReturnType::Default => (TestReturnSignature::default(), input.item.lifetimes.clone()),
@@ -92,66 +132,109 @@ impl TestFnSignature {
                 (sig, lifetimes)
             }
         };
-        let lifetime_params = filter_fn_lifetimes(&item.sig.generics, &lifetimes)?;
+        let lifetime_params = filter_fn_lifetimes(&item.sig.generics, &lifetimes);
+        let lifetime_bounds = collect_lifetime_bounds(&item.sig.generics, &lifetimes);
         Ok(TestFnSignature {
             input,
             output,
             lifetime_params,
+            lifetime_bounds,
+            generic_params,
         })
     }
+
+    // All lifetimes used in the input and the output of the function
+    pub fn lifetimes(&self) -> HashSet<Lifetime> {
+        self.input
+            .item
+            .lifetimes
+            .union(&self.output.item.lifetimes)
+            .cloned()
+            .collect()
+    }
+
+    // Returns a where clause with the lifetime bounds that apply
+    // within the given set of lifetimes, if there are any.
+    pub fn lifetime_where_clause(&self, lifetimes: &HashSet<Lifetime>) -> Option<WhereClause> {
+        let predicates = self
+            .lifetime_bounds
+            .iter()
+            .filter_map(|predicate| restrict_lifetime_bounds(predicate, lifetimes))
+            .collect::<Vec<_>>();
+        if predicates.is_empty() {
+            None
+        } else {
+            Some(parse_quote! { where #(#predicates),* })
+        }
+    }
 }
 
 impl TestInputSignature {
     fn try_build<'a>(inputs: impl IntoIterator<Item = &'a FnArg>) -> syn::Result<Self> {
         let mut lifetime_collector = LifetimeCollector::new(LifetimeSubstMode::Input);
-        let args = inputs
-            .into_iter()
-            .map(|input| match input {
-                FnArg::Typed(arg) => match &*arg.pat {
-                    Pat::Ident(PatIdent {
-                        ident,
-                        mutability: _,
-                        attrs,
-                        by_ref,
-                        subpat,
-                    }) => {
-                        if by_ref.is_some() || subpat.is_some() || !attrs.is_empty() {
-                            return Err(Error::new_spanned(
-                                &arg.pat,
-                                "unsupported features in an argument pattern",
-                            ));
-                        }
-                        let arg_ty = arg.ty.clone();
-                        let mut field_ty = arg_ty.clone();
-                        lifetime_collector.visit_type_mut(&mut field_ty);
-                        Ok(TestFnArg {
-                            attrs: arg.attrs.clone(),
-                            ident: ident.clone(),
-                            arg_ty,
-                            field_ty,
-                        })
-                    }
-                    Pat::Wild(wild) => Err(Error::new_spanned(
-                        wild,
-                        "wildcard pattern not allowed in generic test function input",
-                    )),
-                    _ => Err(Error::new_spanned(
-                        arg,
-                        "unsupported argument pattern in generic test function input",
-                    )),
-                },
-                FnArg::Receiver(_) => Err(Error::new_spanned(
-                    input,
-                    "unexpected receiver argument in a test function",
-                )),
-            })
-            .collect::<syn::Result<_>>()?;
+        let mut args = Vec::new();
+        let mut fixture_positions = Vec::new();
+        for (index, input) in inputs.into_iter().enumerate() {
+            let arg = match input {
+                FnArg::Typed(arg) => arg,
+                FnArg::Receiver(_) => {
+                    return Err(Error::new_spanned(
+                        input,
+                        "unexpected receiver argument in a test function",
+                    ))
+                }
+            };
+            if is_fixture_arg(input)? {
+                fixture_positions.push(index);
+                continue;
+            }
+            let ident = match &*arg.pat {
+                // Bindings by reference and subpatterns only affect
+                // the body of the generic function, as do the
+                // mutability and destructuring patterns.
+                Pat::Ident(PatIdent { ident, .. }) => ident.clone(),
+                _ => format_ident!("_generic_tests_arg{}", index),
+            };
+            let arg_ty = arg.ty.clone();
+            let mut field_ty = arg_ty.clone();
+            lifetime_collector.visit_type_mut(&mut field_ty);
+            args.push(TestFnArg {
+                attrs: arg.attrs.clone(),
+                ident,
+                arg_ty,
+                field_ty,
+            });
+        }
         let lifetimes = lifetime_collector.validate()?;
         Ok(TestInputSignature {
             item: TestSignatureItem { lifetimes },
             args,
+            fixture_positions,
         })
     }
+
+    // The total number of arguments, including fixtures
+    pub fn arg_count(&self) -> usize {
+        self.args.len() + self.fixture_positions.len()
+    }
+}
+
+pub fn is_fixture_attr(attr: &Attribute) -> bool {
+    attr.path().is_ident("fixture")
+}
+
+fn is_fixture_arg(arg: &FnArg) -> syn::Result<bool> {
+    let attrs = match arg {
+        FnArg::Typed(arg) => &arg.attrs,
+        FnArg::Receiver(_) => return Ok(false),
+    };
+    match attrs.iter().find(|attr| is_fixture_attr(attr)) {
+        Some(attr) => {
+            attr.meta.require_path_only()?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
 }
 
 impl Default for TestReturnSignature {
@@ -247,7 +330,12 @@ impl LifetimeCollector {
         let lifetime = match &self.subst_mode {
             Mode::Disabled => return,
             Mode::Output(lifetime) => lifetime,
-            Mode::Input | Mode::Fail => {
+            Mode::Input => {
+                // Each placeholder in the input stands for a distinct lifetime
+                *placeholder = self.add_elided_lifetime();
+                return;
+            }
+            Mode::Fail => {
                 self.errors.add_error(Error::new_spanned(
                     placeholder,
                     "lifetime needs to be disambiguated",
@@ -411,63 +499,212 @@ impl<'a> Drop for LifetimeBindingScope<'a> {
     }
 }
 
-// Checks for any uses of generic type and const parameters and reports
-// an error if found, as this macro can not yet substitute these parameters
-// in test function signatures.
-struct GenericParamCatcher {
-    generic_params: HashSet<Ident>,
+// Collects uses of generic type and const parameters in the signature,
+// checking that associated types of type parameters can be qualified
+// when the parameters are substituted in instantiations.
+struct GenericParamCollector<'a> {
+    generic_params: &'a mut [TestGenericParam],
     errors: ErrorRecord,
 }
 
-impl GenericParamCatcher {
-    fn new(generics: &Generics) -> Self {
-        let generic_params = generics
-            .params
-            .iter()
-            .filter_map(|param| match param {
-                GenericParam::Type(TypeParam { ident, .. }) => Some(ident.clone()),
-                GenericParam::Const(ConstParam { ident, .. }) => Some(ident.clone()),
-                GenericParam::Lifetime(_) => None,
-            })
-            .collect();
-        GenericParamCatcher {
+impl<'a> GenericParamCollector<'a> {
+    fn new(generic_params: &'a mut [TestGenericParam]) -> Self {
+        GenericParamCollector {
             generic_params,
             errors: Default::default(),
         }
     }
-}
 
-impl<'ast> Visit<'ast> for GenericParamCatcher {
-    fn visit_path(&mut self, path: &'ast Path) {
-        const ERROR_MSG: &str =
-            "use of generic parameters in test function signatures is not supported";
-
-        if let Some(ident) = path.get_ident() {
-            if self.generic_params.contains(ident) {
-                self.errors.add_error(Error::new_spanned(ident, ERROR_MSG));
-            }
+    fn check_path_start(&mut self, path: &Path) {
+        if path.leading_colon.is_some() || !path.segments[0].arguments.is_none() {
             return;
         }
-        if path.leading_colon.is_none() && path.segments.len() == 2 {
-            use syn::PathArguments::*;
-            if let (None, None) = (&path.segments[0].arguments, &path.segments[1].arguments) {
-                let suspected_param = &path.segments[0].ident;
-                if self.generic_params.contains(suspected_param) {
-                    self.errors
-                        .add_error(Error::new_spanned(suspected_param, ERROR_MSG));
+        let ident = &path.segments[0].ident;
+        let param = match self.generic_params.iter_mut().find(|p| p.ident == *ident) {
+            Some(param) => param,
+            None => return,
+        };
+        param.in_signature = true;
+        if path.segments.len() > 1 {
+            match &param.kind {
+                TestGenericParamKind::Type { trait_bounds } if trait_bounds.len() == 1 => {}
+                _ => {
+                    self.errors.add_error(Error::new_spanned(
+                        path,
+                        format!(
+                            "associated item of `{}` can not be resolved to a single trait; \
+                            use a qualified path `<{} as Trait>::...`",
+                            ident, ident,
+                        ),
+                    ));
                 }
-                return;
             }
         }
+    }
+}
+
+impl<'a, 'ast> Visit<'ast> for GenericParamCollector<'a> {
+    fn visit_path(&mut self, path: &'ast Path) {
+        self.check_path_start(path);
         visit::visit_path(self, path)
     }
 
     fn visit_type_path(&mut self, type_path: &'ast TypePath) {
         match &type_path.qself {
             None => self.visit_path(&type_path.path),
-            Some(qself) => self.visit_qself(qself),
+            Some(qself) => {
+                // The path is that of the trait, check only its arguments
+                self.visit_qself(qself);
+                for segment in &type_path.path.segments {
+                    self.visit_path_arguments(&segment.arguments);
+                }
+            }
+        }
+    }
+}
+
+// Substitutes paths to the instantiation arguments for uses of generic
+// type and const parameters in types of a test function signature.
+// Associated types of type parameters are qualified with the parameter's
+// trait bound.
+pub struct GenericParamSubst<'a> {
+    generic_params: &'a [TestGenericParam],
+    replacements: Vec<Path>,
+}
+
+impl<'a> GenericParamSubst<'a> {
+    // The replacements are given in the order of `generic_params`
+    pub fn new(generic_params: &'a [TestGenericParam], replacements: Vec<Path>) -> Self {
+        debug_assert_eq!(generic_params.len(), replacements.len());
+        GenericParamSubst {
+            generic_params,
+            replacements,
+        }
+    }
+
+    fn lookup(&self, path: &Path) -> Option<(&'a TestGenericParam, &Path)> {
+        if path.leading_colon.is_some() || !path.segments[0].arguments.is_none() {
+            return None;
+        }
+        let ident = &path.segments[0].ident;
+        let pos = self.generic_params.iter().position(|p| p.ident == *ident)?;
+        Some((&self.generic_params[pos], &self.replacements[pos]))
+    }
+}
+
+impl<'a> VisitMut for GenericParamSubst<'a> {
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        if let Type::Path(TypePath { qself: None, path }) = ty {
+            if let Some((param, replacement)) = self.lookup(path) {
+                if path.segments.len() == 1 {
+                    *ty = Type::Path(TypePath {
+                        qself: None,
+                        path: replacement.clone(),
+                    });
+                    return;
+                }
+                let trait_path = match &param.kind {
+                    TestGenericParamKind::Type { trait_bounds } => &trait_bounds[0],
+                    TestGenericParamKind::Const { .. } => unreachable!(),
+                };
+                let rest = path
+                    .segments
+                    .iter()
+                    .skip(1)
+                    .collect::<Punctuated<_, Token![::]>>();
+                *ty = parse_quote! { <#replacement as #trait_path>::#rest };
+            }
+        }
+        visit_mut::visit_type_mut(self, ty)
+    }
+
+    fn visit_expr_path_mut(&mut self, expr: &mut ExprPath) {
+        if expr.qself.is_none() && expr.path.segments.len() == 1 {
+            if let Some((_, replacement)) = self.lookup(&expr.path) {
+                expr.path = replacement.clone();
+                return;
+            }
+        }
+        visit_mut::visit_expr_path_mut(self, expr)
+    }
+}
+
+fn collect_generic_params(generics: &Generics) -> Vec<TestGenericParam> {
+    let mut params = generics
+        .params
+        .iter()
+        .filter_map(|param| match param {
+            GenericParam::Type(TypeParam { ident, bounds, .. }) => Some(TestGenericParam {
+                ident: ident.clone(),
+                kind: TestGenericParamKind::Type {
+                    trait_bounds: bounds.iter().filter_map(qualifying_trait_path).collect(),
+                },
+                in_signature: false,
+            }),
+            GenericParam::Const(ConstParam { ident, ty, .. }) => Some(TestGenericParam {
+                ident: ident.clone(),
+                kind: TestGenericParamKind::Const { ty: ty.clone() },
+                in_signature: false,
+            }),
+            GenericParam::Lifetime(_) => None,
+        })
+        .collect::<Vec<_>>();
+    if let Some(where_clause) = &generics.where_clause {
+        for predicate in &where_clause.predicates {
+            if let WherePredicate::Type(PredicateType {
+                lifetimes: None,
+                bounded_ty: Type::Path(TypePath { qself: None, path }),
+                bounds,
+                ..
+            }) = predicate
+            {
+                let ident = match path.get_ident() {
+                    Some(ident) => ident,
+                    None => continue,
+                };
+                if let Some(TestGenericParam {
+                    kind: TestGenericParamKind::Type { trait_bounds },
+                    ..
+                }) = params.iter_mut().find(|p| p.ident == *ident)
+                {
+                    trait_bounds.extend(bounds.iter().filter_map(qualifying_trait_path));
+                }
+            }
         }
     }
+    params
+}
+
+// Returns the path of a trait bound usable in a qualified path,
+// with any associated item constraints removed.
+fn qualifying_trait_path(bound: &TypeParamBound) -> Option<Path> {
+    let bound = match bound {
+        TypeParamBound::Trait(bound) => bound,
+        _ => return None,
+    };
+    if !matches!(bound.modifier, TraitBoundModifier::None) {
+        return None;
+    }
+    let mut path = bound.path.clone();
+    if let Some(segment) = path.segments.last_mut() {
+        if let PathArguments::AngleBracketed(args) = &mut segment.arguments {
+            args.args = mem::take(&mut args.args)
+                .into_iter()
+                .filter(|arg| {
+                    matches!(
+                        arg,
+                        GenericArgument::Lifetime(_)
+                            | GenericArgument::Type(_)
+                            | GenericArgument::Const(_)
+                    )
+                })
+                .collect();
+            if args.args.is_empty() {
+                segment.arguments = PathArguments::None;
+            }
+        }
+    }
+    Some(path)
 }
 
 fn validate(sig: &Signature) -> syn::Result<()> {
@@ -489,50 +726,86 @@ fn validate(sig: &Signature) -> syn::Result<()> {
             "variadic arguments are not supported in a generic test function",
         ));
     }
-    let mut catcher = GenericParamCatcher::new(&sig.generics);
+    Ok(())
+}
+
+fn collect_generic_param_uses(
+    sig: &Signature,
+    generic_params: &mut [TestGenericParam],
+) -> syn::Result<()> {
+    let mut collector = GenericParamCollector::new(generic_params);
+    // Fixture arguments do not appear in the instantiated signature
     for arg in &sig.inputs {
-        catcher.visit_fn_arg(arg);
+        if !is_fixture_arg(arg)? {
+            collector.visit_fn_arg(arg);
+        }
     }
     match &sig.output {
         ReturnType::Default => {}
-        ReturnType::Type(_, ty) => catcher.visit_type(ty),
+        ReturnType::Type(_, ty) => collector.visit_type(ty),
     }
-    catcher.errors.check()
+    collector.errors.check()
 }
 
 fn filter_fn_lifetimes(
     generics: &Generics,
     lifetimes_used: &HashSet<Lifetime>,
-) -> syn::Result<Punctuated<Lifetime, Token![,]>> {
-    let lifetimes = generics
+) -> Punctuated<Lifetime, Token![,]> {
+    generics
         .lifetimes()
         .filter(|def| lifetimes_used.contains(&def.lifetime))
-        .map(|def| validate_lifetime_def(&def.lifetime, &def.bounds).map(|()| def.lifetime.clone()))
-        .collect::<syn::Result<_>>()?;
-    if let Some(where_clause) = &generics.where_clause {
-        for predicate in &where_clause.predicates {
-            match predicate {
-                WherePredicate::Lifetime(predicate) => {
-                    if lifetimes_used.contains(&predicate.lifetime) {
-                        validate_lifetime_def(&predicate.lifetime, &predicate.bounds)?;
-                    }
-                }
-                _ => {}
-            }
-        }
-    }
-    Ok(lifetimes)
+        .map(|def| def.lifetime.clone())
+        .collect()
 }
 
-fn validate_lifetime_def<'ast>(
-    _: &'ast Lifetime,
-    bounds: &'ast Punctuated<Lifetime, Token![+]>,
-) -> syn::Result<()> {
-    if !bounds.is_empty() {
-        return Err(Error::new_spanned(
-            bounds,
-            "lifetime bounds are not supported in generic test functions",
-        ));
-    }
-    Ok(())
+// Collects the bounds of lifetime parameters, both inline and in the where
+// clause, leaving out any bounds on lifetimes that are not used
+// in the signature.
+fn collect_lifetime_bounds(
+    generics: &Generics,
+    lifetimes_used: &HashSet<Lifetime>,
+) -> Vec<PredicateLifetime> {
+    let inline_bounds = generics
+        .lifetimes()
+        .filter(|def| !def.bounds.is_empty())
+        .map(|def| PredicateLifetime {
+            lifetime: def.lifetime.clone(),
+            colon_token: Default::default(),
+            bounds: def.bounds.clone(),
+        });
+    let where_bounds = generics
+        .where_clause
+        .iter()
+        .flat_map(|where_clause| &where_clause.predicates)
+        .filter_map(|predicate| match predicate {
+            WherePredicate::Lifetime(predicate) => Some(predicate.clone()),
+            _ => None,
+        });
+    inline_bounds
+        .chain(where_bounds)
+        .filter_map(|predicate| restrict_lifetime_bounds(&predicate, lifetimes_used))
+        .collect()
+}
+
+fn restrict_lifetime_bounds(
+    predicate: &PredicateLifetime,
+    lifetimes: &HashSet<Lifetime>,
+) -> Option<PredicateLifetime> {
+    if !lifetimes.contains(&predicate.lifetime) {
+        return None;
+    }
+    let bounds = predicate
+        .bounds
+        .iter()
+        .filter(|bound| bound.ident == "static" || lifetimes.contains(bound))
+        .cloned()
+        .collect::<Punctuated<_, Token![+]>>();
+    if bounds.is_empty() {
+        return None;
+    }
+    Some(PredicateLifetime {
+        lifetime: predicate.lifetime.clone(),
+        colon_token: predicate.colon_token,
+        bounds,
+    })
 }
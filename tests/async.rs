@@ -1,5 +1,7 @@
 #![deny(unused)]
 #![warn(clippy::all)]
+#![allow(clippy::extra_unused_type_parameters)]
+#![allow(clippy::multiple_bound_locations)]
 
 #[generic_tests::define(attrs(tokio::test))]
 mod async_tests {
@@ -74,3 +76,87 @@ mod async_tests {
     #[instantiate_tests(<Bytes>)]
     mod inst {}
 }
+
+#[generic_tests::define(attrs(tokio::test))]
+mod runtimes {
+    use tokio::runtime::{Handle, RuntimeFlavor};
+
+    #[tokio::test]
+    async fn runs_on_expected_runtime<const MULTI_THREAD: bool>() {
+        let flavor = Handle::current().runtime_flavor();
+        assert_eq!(flavor == RuntimeFlavor::MultiThread, MULTI_THREAD);
+    }
+
+    #[instantiate_tests(<false>)]
+    mod current_thread {}
+
+    #[instantiate_tests(
+        <true>,
+        attrs(tokio::test(flavor = "multi_thread", worker_threads = 2)),
+    )]
+    mod multi_thread {}
+}
+
+#[generic_tests::define(attrs(tokio::test))]
+mod async_trait_suite {
+    use tokio::io::{self, AsyncReadExt};
+
+    trait ReadContract {
+        type Reader: tokio::io::AsyncRead + Unpin + From<&'static [u8]>;
+
+        #[tokio::test]
+        async fn read_to_end() -> io::Result<()> {
+            let mut reader = Self::Reader::from(b"Hello");
+            let mut buf = Vec::new();
+            reader.read_to_end(&mut buf).await?;
+            assert_eq!(buf, b"Hello");
+            Ok(())
+        }
+    }
+
+    struct SliceReader;
+
+    impl ReadContract for SliceReader {
+        type Reader = &'static [u8];
+    }
+}
+
+#[generic_tests::define(attrs(tokio::test, should_panic))]
+mod async_setup_teardown {
+    use std::cell::Cell;
+
+    // The tests run on the current-thread runtime of the test thread
+    thread_local! {
+        static STATE: Cell<&'static str> = const { Cell::new("") };
+    }
+
+    #[generic_setup]
+    async fn setup<T>() {
+        tokio::task::yield_now().await;
+        STATE.with(|state| state.set("set up"));
+    }
+
+    #[generic_teardown]
+    async fn teardown<T>() {
+        tokio::task::yield_now().await;
+        if STATE.with(Cell::get) == "panicked" {
+            panic!("torn down after panic");
+        }
+    }
+
+    #[tokio::test]
+    async fn setup_is_awaited<T>() {
+        assert_eq!(STATE.with(Cell::get), "set up");
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "torn down after panic")]
+    async fn teardown_is_awaited_on_panic<T>() {
+        tokio::task::yield_now().await;
+        STATE.with(|state| state.set("panicked"));
+        panic!("test failed");
+    }
+
+    #[instantiate_tests(<()>)]
+    mod unit {}
+}
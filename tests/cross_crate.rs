@@ -0,0 +1,34 @@
+#![deny(unused)]
+#![warn(clippy::all)]
+#![allow(clippy::extra_unused_type_parameters)]
+
+use exported_suites::{Codec, CodecError};
+
+struct Utf8;
+
+impl Codec for Utf8 {
+    fn encode(s: &str) -> Vec<u8> {
+        s.as_bytes().to_vec()
+    }
+
+    fn decode(bytes: &[u8]) -> Result<String, CodecError> {
+        String::from_utf8(bytes.to_vec()).map_err(|_| CodecError)
+    }
+}
+
+// `crate` paths in the suite refer to the crate exporting it
+#[generic_tests::instantiate(exported_suites::codec, <Utf8>)]
+mod utf8 {}
+
+#[generic_tests::test_with(exported_suites::codec)]
+struct Latin1;
+
+impl Codec for Latin1 {
+    fn encode(s: &str) -> Vec<u8> {
+        s.chars().map(|c| c as u8).collect()
+    }
+
+    fn decode(bytes: &[u8]) -> Result<String, CodecError> {
+        Ok(bytes.iter().map(|&b| b as char).collect())
+    }
+}
@@ -0,0 +1,54 @@
+//! Generic test suites exported for instantiation in the tests
+//! of `generic-tests`.
+
+#![deny(unused)]
+#![warn(clippy::all)]
+
+use std::fmt::{self, Display};
+
+pub const GREETING: &str = "Hello";
+
+#[derive(Debug)]
+pub struct CodecError;
+
+impl Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("codec error")
+    }
+}
+
+pub trait Codec {
+    fn encode(s: &str) -> Vec<u8>;
+    fn decode(bytes: &[u8]) -> Result<String, CodecError>;
+}
+
+#[generic_tests::define(export)]
+pub mod codec {
+    use crate::Codec;
+
+    #[generic_setup]
+    fn setup<C: Codec>() {
+        assert!(!C::encode(crate::GREETING).is_empty());
+    }
+
+    #[test]
+    fn round_trip<C: Codec>() -> Result<(), crate::CodecError> {
+        let decoded = C::decode(&C::encode(crate::GREETING))?;
+        assert_eq!(decoded, crate::GREETING);
+        Ok(())
+    }
+
+    #[test]
+    #[generic_test(case(crate::GREETING), case(""))]
+    fn decodes<C: Codec>(s: &str) {
+        assert_eq!(C::decode(s.as_bytes()).unwrap(), s);
+    }
+
+    pub(crate) mod nested {
+        #[test]
+        fn empty<C: crate::Codec>() -> Result<(), crate::CodecError> {
+            assert!(C::decode(b"")?.is_empty());
+            Ok(())
+        }
+    }
+}
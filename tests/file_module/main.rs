@@ -0,0 +1,13 @@
+// Out-of-line modules in the input of attribute macros are not supported
+// by stable Rust. To run these tests, use a nightly toolchain,
+// `RUSTFLAGS="--cfg nightly"`, and the `file-modules` feature.
+#![cfg(nightly)]
+#![feature(proc_macro_hygiene)]
+#![deny(unused)]
+
+#[generic_tests::define]
+mod suite;
+
+#[generic_tests::define]
+#[path = "suite/renamed.rs"]
+mod renamed_suite;
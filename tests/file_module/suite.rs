@@ -0,0 +1,19 @@
+//! Generic tests loaded from a file
+
+mod helpers;
+
+use helpers::hello;
+
+#[test]
+fn converts_from_str<T>()
+where
+    T: From<&'static str> + AsRef<str>,
+{
+    assert_eq!(T::from(hello()).as_ref(), hello());
+}
+
+#[instantiate_tests(<String>)]
+mod string {}
+
+#[instantiate_tests(<Box<str>>)]
+mod boxed_str {}
@@ -0,0 +1,12 @@
+#[test]
+fn is_empty_by_default<T: Default + AsRef<[u8]>>() {
+    assert!(T::default().as_ref().is_empty());
+}
+
+#[instantiate_tests(<Vec<u8>>)]
+mod vec {}
+
+// The macro makes the module inline; rustfmt would look for its file
+#[rustfmt::skip]
+#[instantiate_tests(<String>)]
+mod string;
@@ -0,0 +1,201 @@
+mod suites {
+    #[generic_tests::define]
+    pub mod codec {
+        pub(crate) use std::borrow::Cow;
+
+        pub trait Codec {
+            fn encode(s: &str) -> Vec<u8>;
+            fn decode(bytes: &[u8]) -> Cow<'_, str>;
+        }
+
+        #[test]
+        fn round_trip<C: Codec>() {
+            let encoded = C::encode("Hello");
+            assert_eq!(C::decode(&encoded), "Hello");
+        }
+
+        #[test]
+        #[generic_test(case("Hello"), case("world"))]
+        fn decodes<C: Codec>(s: &str) {
+            assert_eq!(C::decode(s.as_bytes()), s);
+        }
+
+        #[test]
+        fn empty<C: Codec>() {
+            assert!(C::encode("").is_empty());
+        }
+
+        #[test]
+        fn returns_cow<C: Codec>() -> Result<(), Cow<'static, str>> {
+            if C::decode(b"").is_empty() {
+                Ok(())
+            } else {
+                Err("non-empty".into())
+            }
+        }
+
+        mod failures {
+            use super::Codec;
+
+            #[test]
+            #[should_panic]
+            fn panics_on_purpose<C: Codec>() {
+                C::decode(b"\xFF");
+            }
+        }
+    }
+
+    // Instantiating an exported suite in the same crate works the same way
+    #[generic_tests::define(export)]
+    pub mod exported {
+        #[test]
+        fn default_is_empty<T: Default + PartialEq + super::IsEmpty>() {
+            assert!(T::default().is_empty());
+        }
+
+        mod nested {
+            #[test]
+            fn clone_eq<T: Clone + Default + PartialEq + std::fmt::Debug>() {
+                let value = T::default();
+                assert_eq!(value.clone(), value);
+            }
+        }
+    }
+
+    pub trait IsEmpty {
+        fn is_empty(&self) -> bool;
+    }
+
+    impl IsEmpty for String {
+        fn is_empty(&self) -> bool {
+            str::is_empty(self)
+        }
+    }
+
+    #[generic_tests::define]
+    pub mod fixtured {
+        #[test]
+        fn default_eq<T: Default + PartialEq + std::fmt::Debug>(#[fixture] value: T) {
+            assert_eq!(value, T::default());
+        }
+    }
+
+    #[generic_tests::define]
+    pub mod sized {
+        #[generic_setup]
+        fn setup<T, const N: usize>() {
+            assert!(N <= 4, "unexpectedly large size");
+        }
+
+        #[test]
+        fn size<T, const N: usize>() {
+            assert_eq!(std::mem::size_of::<T>(), N);
+        }
+    }
+}
+
+mod codecs {
+    use crate::suites::codec::Codec;
+    use std::borrow::Cow;
+
+    pub struct Utf8;
+
+    impl Codec for Utf8 {
+        fn encode(s: &str) -> Vec<u8> {
+            s.as_bytes().to_vec()
+        }
+
+        fn decode(bytes: &[u8]) -> Cow<'_, str> {
+            Cow::Borrowed(std::str::from_utf8(bytes).unwrap())
+        }
+    }
+
+    pub struct Lossy;
+
+    impl Codec for Lossy {
+        fn encode(s: &str) -> Vec<u8> {
+            s.as_bytes().to_vec()
+        }
+
+        fn decode(bytes: &[u8]) -> Cow<'_, str> {
+            if bytes.is_empty() {
+                return Cow::Borrowed("");
+            }
+            let decoded = String::from_utf8_lossy(bytes);
+            if decoded.contains('\u{FFFD}') {
+                panic!("invalid UTF-8");
+            }
+            decoded
+        }
+    }
+
+    #[generic_tests::instantiate(crate::suites::codec, <Utf8>)]
+    mod utf8_tests {}
+
+    #[generic_tests::instantiate(super::suites::codec, <Lossy>, only(round_trip, panics_on_purpose))]
+    mod lossy_tests {
+        #[test]
+        fn local_test() {}
+    }
+}
+
+#[generic_tests::instantiate(suites::codec, <C = codecs::Utf8>)]
+mod named {}
+
+#[generic_tests::instantiate(suites::sized, matrix(<u8 | i8>, <1>))]
+mod byte_sized {}
+
+#[generic_tests::instantiate(suites::sized, <u16, _>)]
+mod u16_size {
+    #[instantiate_tests(<_, 2>)]
+    mod two {}
+}
+
+#[generic_tests::instantiate(suites::sized, <[u8; 3], 3>, attrs(test), ignore(size = "checked elsewhere"))]
+mod ignored {}
+
+#[generic_tests::instantiate(crate::suites::exported, <String>)]
+mod exported_string {}
+
+fn empty_string() -> String {
+    String::new()
+}
+
+#[generic_tests::instantiate(suites::fixtured, <String>, fixture = empty_string())]
+mod fixtured_string {}
+
+mod types {
+    use crate::suites::codec::Codec;
+    use std::borrow::Cow;
+
+    #[generic_tests::test_with(crate::suites::codec)]
+    #[generic_tests::test_with(crate::suites::sized, <Ascii, 0>)]
+    pub struct Ascii;
+
+    impl Codec for Ascii {
+        fn encode(s: &str) -> Vec<u8> {
+            s.bytes().filter(u8::is_ascii).collect()
+        }
+
+        fn decode(bytes: &[u8]) -> Cow<'_, str> {
+            assert!(bytes.is_ascii(), "not ASCII");
+            String::from_utf8_lossy(bytes)
+        }
+    }
+
+    #[generic_tests::test_with(crate::suites::codec, except(panics_on_purpose))]
+    pub enum Latin1 {}
+
+    impl Codec for Latin1 {
+        fn encode(s: &str) -> Vec<u8> {
+            s.chars().map(|c| c as u8).collect()
+        }
+
+        fn decode(bytes: &[u8]) -> Cow<'_, str> {
+            bytes.iter().map(|&b| b as char).collect::<String>().into()
+        }
+    }
+
+    #[generic_tests::test_with(crate::suites::sized, <Pair<u16>, 4>)]
+    pub type Pair<T> = (T, T);
+}
@@ -1,5 +1,7 @@
 #![deny(unused)]
 #![warn(clippy::all)]
+#![allow(clippy::extra_unused_type_parameters)]
+#![allow(clippy::multiple_bound_locations)]
 
 #[generic_tests::define]
 mod simple {
@@ -7,6 +9,7 @@ mod simple {
     use std::fmt::Debug;
 
     #[test]
+    #[allow(clippy::needless_maybe_sized)]
     fn equates_to_str<S: From<&'static str>>()
     where
         S: ?Sized + PartialEq<str> + Debug,
@@ -23,6 +26,947 @@ mod simple {
     mod cow {}
 }
 
+#[generic_tests::define]
+mod matrix {
+    use std::borrow::Cow;
+    use std::fmt::Debug;
+
+    #[test]
+    fn converts_with_len<S, L>()
+    where
+        S: From<&'static str> + AsRef<str> + Debug,
+        L: TryFrom<usize> + Into<u64>,
+        L::Error: Debug,
+    {
+        let s = S::from("ab");
+        let len = L::try_from(s.as_ref().len()).unwrap();
+        assert_eq!(len.into(), 2);
+    }
+
+    #[instantiate_tests(matrix(<String | Cow<'static, str>>, <u16 | u32 | u64>))]
+    mod types {}
+
+    mod nested {
+        #[instantiate_tests(matrix(<String>, <Len>))]
+        mod local_type {}
+
+        type Len = u8;
+    }
+}
+
+#[generic_tests::define(matrix_limit = 4)]
+mod matrix_limit {
+    #[test]
+    fn sizes_array<const N: usize, const M: usize>() {
+        let a = [[0u8; N]; M];
+        assert_eq!(a.len() * a[0].len(), N * M);
+    }
+
+    #[instantiate_tests(matrix(<1 | 2>, <3 | 4>))]
+    mod sizes {}
+}
+
+#[generic_tests::define(instantiate(
+    string = <String>,
+    <Cow<'static, str>>,
+    <Vec<u8>>,
+    matrix = matrix(<String | Local>),
+))]
+mod root_instantiations {
+    use std::borrow::Cow;
+    use std::fmt::Debug;
+
+    #[derive(Debug, PartialEq)]
+    struct Local(&'static str);
+
+    impl From<&'static str> for Local {
+        fn from(s: &'static str) -> Self {
+            Local(s)
+        }
+    }
+
+    #[test]
+    fn converts_from_str<T>()
+    where
+        T: From<&'static str> + Debug + PartialEq,
+    {
+        assert_eq!(T::from("ab"), T::from("ab"));
+    }
+
+    mod check_names {
+        #[allow(unused_imports)]
+        use super::{cow_static_str, matrix, string, vec_u8};
+    }
+}
+
+#[generic_tests::define]
+mod named_args {
+    #[test]
+    fn type_then_const<T: Default + Clone + PartialEq, const N: usize>() {
+        let v = vec![T::default(); N];
+        assert_eq!(v.len(), N);
+        assert!(v.iter().all(|x| *x == T::default()));
+    }
+
+    #[test]
+    fn const_then_type<const N: usize, T: Default + Copy + PartialEq>() {
+        let a = [T::default(); N];
+        assert_eq!(a.len(), N);
+        assert!(a.iter().all(|x| *x == T::default()));
+    }
+
+    #[instantiate_tests(<T = u8, N = 4>)]
+    mod u8_4 {}
+
+    #[instantiate_tests(<N = 16, T = bool>)]
+    mod bool_16 {}
+
+    #[instantiate_tests(matrix(<N = 1 | 2>, <T = char | ()>))]
+    mod matrix {}
+}
+
+#[generic_tests::define]
+mod param_subsets {
+    use bytes::{Buf, Bytes};
+
+    #[test]
+    fn buf_only<B: Buf + From<&'static str>>() {
+        let b = B::from("Hello");
+        assert_eq!(b.remaining(), 5);
+    }
+
+    #[test]
+    fn capacity_only<const CAP: usize>() {
+        let v = Vec::<u8>::with_capacity(CAP);
+        assert!(v.capacity() >= CAP);
+    }
+
+    #[test]
+    fn both<const CAP: usize, B>()
+    where
+        B: Buf + From<&'static str>,
+    {
+        let mut b = B::from("Hello, world!");
+        let mut v = Vec::with_capacity(CAP);
+        while b.has_remaining() && v.len() < CAP {
+            v.push(b.get_u8());
+        }
+        assert_eq!(v.len(), CAP.min(13));
+    }
+
+    #[test]
+    fn no_params_used<T>() {}
+
+    #[instantiate_tests(<B = Bytes, CAP = 4, T = ()>)]
+    mod bytes_4 {}
+
+    #[instantiate_tests(matrix(<B = Bytes>, <CAP = 1 | 64>, <T = ()>))]
+    mod matrix {}
+}
+
+#[generic_tests::define]
+mod filters {
+    #[test]
+    fn len<T>()
+    where
+        T: From<&'static [u8]> + AsRef<[u8]>,
+    {
+        let v = T::from(b"Hello");
+        assert_eq!(v.as_ref().len(), 5);
+    }
+
+    #[test]
+    fn extend<T>()
+    where
+        T: Default + Extend<u8> + AsRef<[u8]>,
+    {
+        let mut v = T::default();
+        v.extend([1, 2, 3]);
+        assert_eq!(v.as_ref(), [1, 2, 3]);
+    }
+
+    #[instantiate_tests(<Vec<u8>>)]
+    mod vec {}
+
+    #[instantiate_tests(<&'static [u8]>, only(len))]
+    mod slice {}
+
+    #[instantiate_tests(<Box<[u8]>>, except(extend))]
+    mod boxed_slice {}
+
+    #[instantiate_tests(matrix(<Vec<u8> | &'static [u8]>), except(extend))]
+    mod matrix {}
+}
+
+#[generic_tests::define]
+mod inst_test_attrs {
+    use std::fmt::Debug;
+    use std::str::FromStr;
+
+    #[test]
+    fn parses_number<T: FromStr>()
+    where
+        T::Err: Debug,
+    {
+        "300".parse::<T>().unwrap();
+    }
+
+    #[test]
+    #[should_panic]
+    fn fails_to_parse_empty<T: FromStr>()
+    where
+        T::Err: Debug,
+    {
+        "".parse::<T>().unwrap();
+    }
+
+    #[instantiate_tests(<u32>)]
+    mod u32 {}
+
+    #[instantiate_tests(<u8>, should_panic(parses_number))]
+    mod u8 {}
+
+    #[instantiate_tests(
+        <i8>,
+        should_panic(
+            parses_number = "PosOverflow",
+            fails_to_parse_empty = "Empty",
+        )
+    )]
+    mod i8 {}
+
+    #[instantiate_tests(<bool>, ignore(parses_number = "not a number type"))]
+    mod bool {}
+}
+
+// The instantiations gated with `any()` would not compile if they were
+// not configured out
+#[generic_tests::define(instantiate(
+    unit = <()> cfg(all()),
+    missing = <Missing> cfg(any()),
+))]
+mod cfg_instantiations {
+    #[derive(Default)]
+    struct NotClone;
+
+    #[test]
+    fn default<T: Default>() {
+        T::default();
+    }
+
+    #[test]
+    fn clone<T: Default + Clone>() {
+        let _ = T::default().clone();
+    }
+
+    #[instantiate_tests(<Missing>, cfg(any()))]
+    mod missing_in_attr {}
+
+    #[instantiate_tests(matrix(<u8 | Missing>), cfg(any()))]
+    mod matrix {}
+
+    #[instantiate_tests(<NotClone>, test_cfg(clone(any())))]
+    mod not_clone {}
+
+    #[instantiate_tests(<u8>, cfg(all()), test_cfg(clone(all())))]
+    mod u8 {}
+}
+
+#[generic_tests::define]
+mod non_empty_inst_modules {
+    pub trait Greet {
+        fn greet() -> String;
+    }
+
+    #[test]
+    fn greets<T: Greet>() {
+        assert!(T::greet().starts_with("Hello"));
+    }
+
+    #[instantiate_tests(<english::English>)]
+    mod english {
+        use super::Greet;
+
+        pub struct English;
+
+        impl Greet for English {
+            fn greet() -> String {
+                "Hello".into()
+            }
+        }
+
+        #[test]
+        fn local_test() {
+            assert_eq!(English::greet(), "Hello");
+        }
+    }
+
+    #[instantiate_tests(matrix(<matrix::helpers::Cheerful | matrix::helpers::Formal>))]
+    mod matrix {
+        pub mod helpers {
+            use super::super::Greet;
+
+            pub struct Cheerful;
+            pub struct Formal;
+
+            impl Greet for Cheerful {
+                fn greet() -> String {
+                    "Hello!".into()
+                }
+            }
+
+            impl Greet for Formal {
+                fn greet() -> String {
+                    "Hello, sir".into()
+                }
+            }
+        }
+    }
+}
+
+#[generic_tests::define]
+mod partial_instantiation {
+    #[derive(Clone, Default, PartialEq)]
+    pub struct Local;
+
+    #[test]
+    fn fill<T: Clone + Default + PartialEq, const N: usize>() {
+        let v = vec![T::default(); N];
+        assert_eq!(v.len(), N);
+        assert!(v.iter().all(|x| *x == T::default()));
+    }
+
+    #[instantiate_tests(<String, _>)]
+    mod string {
+        #[instantiate_tests(<_, 16>)]
+        mod n16 {}
+
+        #[instantiate_tests(<_, 4096>)]
+        mod n4096 {}
+    }
+
+    #[instantiate_tests(<_, 1>)]
+    mod one {
+        use super::Local;
+
+        #[instantiate_tests(<Local, _>)]
+        mod local {}
+
+        mod nested {
+            #[instantiate_tests(<u8, _>)]
+            mod u8 {}
+        }
+
+        #[instantiate_tests(matrix(<u16 | u32>, <_>))]
+        mod matrix {}
+    }
+
+    #[instantiate_tests(<T = Local, N = _>)]
+    mod named {
+        #[instantiate_tests(<N = 2>)]
+        mod n2 {}
+    }
+}
+
+// Items in the partial instantiation modules shadow the names
+// of the arguments given for the enclosing instantiations
+#[generic_tests::define]
+mod partial_instantiation_scope {
+    type Elem = u8;
+    const LEN: usize = 2;
+
+    #[test]
+    fn byte_array<T, const N: usize>() {
+        assert_eq!(std::mem::size_of::<[T; N]>(), N);
+    }
+
+    #[instantiate_tests(<Elem, _>)]
+    mod elem {
+        #[allow(dead_code)]
+        type Elem = u64;
+
+        #[instantiate_tests(<_, 1>)]
+        mod one {}
+
+        mod nested {
+            #[instantiate_tests(<_, 3>)]
+            mod three {}
+        }
+
+        #[instantiate_tests(<_, _>)]
+        mod pass_through {
+            #[allow(dead_code)]
+            type Elem = u32;
+
+            #[instantiate_tests(<_, 4>)]
+            mod four {}
+        }
+    }
+
+    #[instantiate_tests(<T = _, N = LEN>)]
+    mod len {
+        #[allow(dead_code)]
+        const LEN: usize = 3;
+
+        #[instantiate_tests(<T = u8>)]
+        mod u8 {}
+    }
+}
+
+#[generic_tests::define]
+mod non_generic_tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static SANITY_RUNS: AtomicUsize = AtomicUsize::new(0);
+
+    #[test]
+    fn sanity() {
+        assert_eq!(SANITY_RUNS.fetch_add(1, Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "kept as is")]
+    fn attrs_are_kept() {
+        panic!("kept as is")
+    }
+
+    #[test]
+    fn generic<T: Default>() {
+        let _ = T::default();
+    }
+
+    #[instantiate_tests(<()>)]
+    mod unit {}
+
+    #[instantiate_tests(<String>)]
+    mod string {}
+}
+
+#[generic_tests::define]
+mod nested_suites {
+    use bytes::{Buf, Bytes};
+
+    fn sample<T: From<&'static [u8]>>() -> T {
+        T::from(b"Hello, world!")
+    }
+
+    #[test]
+    fn root_level<T: Buf + From<&'static [u8]>>() {
+        assert!(sample::<T>().has_remaining());
+    }
+
+    mod read {
+        use super::sample;
+        use bytes::Buf;
+
+        #[test]
+        fn exact_fill<T: Buf + From<&'static [u8]>>() {
+            let mut buf = [0; 5];
+            sample::<T>().copy_to_slice(&mut buf);
+            assert_eq!(&buf, b"Hello");
+        }
+
+        #[test]
+        fn not_generic() {}
+
+        mod chunks {
+            use super::super::sample;
+            use bytes::Buf;
+
+            #[test]
+            fn first_chunk<T: Buf + From<&'static [u8]>>() -> Result<(), Box<dyn std::error::Error>>
+            {
+                let buf = sample::<T>();
+                assert!(!buf.chunk().is_empty());
+                Ok(())
+            }
+        }
+    }
+
+    mod advance {
+        use super::sample;
+        use bytes::Buf;
+
+        #[test]
+        fn skip<T: Buf + From<&'static [u8]>>() {
+            let mut buf = sample::<T>();
+            buf.advance(7);
+            assert_eq!(buf.chunk(), b"world!");
+        }
+
+        #[cfg(not(test))]
+        mod disabled {
+            #[test]
+            fn not_instantiated<T>() {
+                panic!("unexpectedly enabled")
+            }
+        }
+    }
+
+    mod helpers {
+        #[allow(dead_code)]
+        pub fn no_tests_here() {}
+    }
+
+    #[instantiate_tests(<Bytes>)]
+    mod shared {}
+
+    #[instantiate_tests(<&'static [u8]>)]
+    mod slice {}
+}
+
+mod contracts {
+    use std::io::{self, Cursor, Read, Seek, SeekFrom};
+
+    struct MemFile(Cursor<Vec<u8>>);
+
+    impl From<Vec<u8>> for MemFile {
+        fn from(data: Vec<u8>) -> Self {
+            MemFile(Cursor::new(data))
+        }
+    }
+
+    impl Read for MemFile {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    impl Seek for MemFile {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.0.seek(pos)
+        }
+    }
+
+    #[generic_tests::define]
+    mod read_contract {
+        use std::io::Read;
+
+        #[test]
+        fn read_to_end<T: Read + From<Vec<u8>>>() -> std::io::Result<()> {
+            let mut buf = Vec::new();
+            T::from(b"Hello".to_vec()).read_to_end(&mut buf)?;
+            assert_eq!(buf, b"Hello");
+            Ok(())
+        }
+
+        mod exact {
+            use std::io::Read;
+
+            #[test]
+            fn read_exact<T: Read + From<Vec<u8>>>() {
+                let mut buf = [0; 4];
+                T::from(b"Hello".to_vec()).read_exact(&mut buf).unwrap();
+                assert_eq!(&buf, b"Hell");
+            }
+        }
+    }
+
+    #[generic_tests::define]
+    mod seek_contract {
+        use std::io::{Seek, SeekFrom};
+
+        #[test]
+        fn seek_end<T: Seek + From<Vec<u8>>>() {
+            let pos = T::from(b"Hello".to_vec()).seek(SeekFrom::End(0)).unwrap();
+            assert_eq!(pos, 5);
+        }
+    }
+
+    #[generic_tests::define(include(super::read_contract, super::seek_contract))]
+    mod file_like_contract {
+        use std::io::{Read, Seek, SeekFrom};
+
+        #[test]
+        fn seek_then_read<T: Read + Seek + From<Vec<u8>>>() {
+            let mut file = T::from(b"Hello".to_vec());
+            file.seek(SeekFrom::Start(1)).unwrap();
+            let mut buf = String::new();
+            file.read_to_string(&mut buf).unwrap();
+            assert_eq!(buf, "ello");
+        }
+
+        #[instantiate_tests(<crate::contracts::MemFile>)]
+        mod mem_file {}
+
+        #[instantiate_tests(<crate::contracts::MemFile>, only(read_exact, seek_then_read))]
+        mod selected {}
+    }
+
+    #[generic_tests::define(include(super::file_like_contract))]
+    mod nested_include {
+        #[instantiate_tests(<crate::contracts::MemFile>)]
+        mod mem_file {}
+    }
+}
+
+#[generic_tests::define]
+mod trait_suites {
+    use bytes::{Buf, Bytes};
+
+    trait BufContract {
+        type Buf: Buf + From<&'static [u8]>;
+        type Error: From<&'static str> + std::fmt::Debug;
+
+        fn sample() -> Self::Buf {
+            Self::Buf::from(b"Hello")
+        }
+
+        #[test]
+        fn remaining() {
+            assert_eq!(Self::sample().remaining(), 5);
+        }
+
+        #[test]
+        fn advance() {
+            let mut buf = Self::sample();
+            buf.advance(2);
+            assert_eq!(buf.chunk(), b"llo");
+        }
+
+        #[test]
+        fn fallible() -> Result<(), Self::Error> {
+            if Self::sample().has_remaining() {
+                Ok(())
+            } else {
+                Err("empty".into())
+            }
+        }
+
+        #[test]
+        #[should_panic]
+        fn out_of_bounds() {
+            Self::sample().advance(6);
+        }
+    }
+
+    struct BytesImpl;
+
+    impl BufContract for BytesImpl {
+        type Buf = Bytes;
+        type Error = String;
+
+        fn advance() {
+            let mut buf = Self::sample();
+            buf.advance(5);
+            assert!(!buf.has_remaining());
+        }
+    }
+
+    trait CloneContract {
+        type Value: Clone + PartialEq + std::fmt::Debug + From<&'static [u8]>;
+
+        #[test]
+        fn clone_eq() {
+            let value = Self::Value::from(b"Hello");
+            assert_eq!(value.clone(), value);
+        }
+    }
+
+    // A type can implement several suite traits
+    impl CloneContract for BytesImpl {
+        type Value = Bytes;
+    }
+
+    mod slices {
+        use super::BufContract;
+
+        pub struct Slice;
+
+        impl BufContract for Slice {
+            type Buf = &'static [u8];
+            type Error = Box<dyn std::error::Error>;
+        }
+    }
+}
+
+// Implementations of unrelated traits with the same name as a suite trait
+// are not instantiated
+#[generic_tests::define]
+mod trait_suite_same_name {
+    trait Default {
+        fn value() -> u8;
+
+        #[test]
+        fn is_zero() {
+            assert_eq!(Self::value(), 0);
+        }
+    }
+
+    struct Zero;
+
+    impl self::Default for Zero {
+        fn value() -> u8 {
+            0
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Other;
+
+    impl std::default::Default for Other {
+        fn default() -> Self {
+            Other
+        }
+    }
+
+    #[test]
+    fn other_is_default() {
+        assert_eq!(<Other as std::default::Default>::default(), Other);
+    }
+}
+
+#[generic_tests::define(params(<T: Buf + From<&'static [u8]>, const N: usize>))]
+mod module_params {
+    use bytes::{Buf, Bytes};
+
+    #[test]
+    fn remaining() {
+        let buf = T::from(b"Hello");
+        assert_eq!(buf.remaining(), 5);
+    }
+
+    #[test]
+    fn advance() {
+        let mut buf = T::from(b"Hello");
+        buf.advance(N);
+        assert_eq!(buf.remaining(), 5 - N);
+    }
+
+    #[test]
+    #[generic_test(no_params)]
+    fn plain() {
+        assert_eq!(Bytes::from_static(b"Hello").len(), 5);
+    }
+
+    #[instantiate_tests(<Bytes, 2>)]
+    mod bytes_2 {
+        // `plain` is not instantiated, so its name is free for helper items
+        #[allow(dead_code)]
+        fn plain() {}
+    }
+
+    #[instantiate_tests(<&'static [u8], 5>)]
+    mod slice_5 {}
+}
+
+#[generic_tests::define(params(<T> where T: Default + AsRef<[u8]>))]
+mod module_params_where_clause {
+    #[test]
+    fn is_empty() {
+        assert!(T::default().as_ref().is_empty());
+    }
+
+    #[test]
+    fn with_own_param<const N: usize>() {
+        assert!(T::default().as_ref().len() < N);
+    }
+
+    #[instantiate_tests(<T = Vec<u8>, N = 1>)]
+    mod vec {}
+}
+
+#[generic_tests::define]
+mod setup_teardown {
+    use std::any;
+    use std::cell::RefCell;
+
+    thread_local! {
+        static LOG: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn log(event: impl Into<String>) {
+        LOG.with(|log| log.borrow_mut().push(event.into()));
+    }
+
+    fn logged() -> Vec<String> {
+        LOG.with(|log| log.borrow().clone())
+    }
+
+    #[generic_setup]
+    fn setup<T>() {
+        log(format!("setup {}", any::type_name::<T>()));
+    }
+
+    #[generic_teardown]
+    fn teardown<T>() {
+        let events = logged();
+        assert_eq!(events[0], format!("setup {}", any::type_name::<T>()));
+        if events.last().unwrap() == "panicked" {
+            panic!("torn down after panic");
+        }
+    }
+
+    #[test]
+    fn setup_is_called<T>() {
+        assert_eq!(logged(), [format!("setup {}", any::type_name::<T>())]);
+    }
+
+    #[test]
+    #[should_panic(expected = "torn down after panic")]
+    fn teardown_is_called_on_panic<T>() {
+        log("panicked");
+        panic!("test failed");
+    }
+
+    #[test]
+    fn result_is_returned<T>() -> Result<(), String> {
+        Ok(())
+    }
+
+    mod nested {
+        use super::{log, logged};
+
+        #[generic_setup]
+        fn inner_setup<T>() {
+            log("inner setup");
+        }
+
+        #[generic_teardown]
+        fn inner_teardown<T>() {
+            log("inner teardown");
+        }
+
+        #[test]
+        fn outer_setup_is_called_first<T>() {
+            assert_eq!(logged()[1..], ["inner setup"]);
+        }
+    }
+
+    #[instantiate_tests(<u8>)]
+    mod byte {}
+
+    #[instantiate_tests(<String>)]
+    mod string {}
+}
+
+#[generic_tests::define]
+mod setup_named_params {
+    use std::cell::Cell;
+
+    thread_local! {
+        static SIZE: Cell<usize> = const { Cell::new(0) };
+    }
+
+    #[generic_setup]
+    fn setup<T>() {
+        SIZE.with(|size| size.set(std::mem::size_of::<T>()));
+    }
+
+    #[test]
+    fn size_is_set<T, const N: usize>() {
+        assert_eq!(SIZE.with(Cell::get), N);
+    }
+
+    #[instantiate_tests(<T = u32, N = 4>)]
+    mod word {}
+}
+
+#[generic_tests::define]
+mod fixtures {
+    use bytes::BytesMut;
+
+    fn hello() -> Vec<u8> {
+        b"Hello".to_vec()
+    }
+
+    #[test]
+    fn fixture_is_passed<T: AsRef<[u8]>>(#[fixture] buf: T) {
+        assert_eq!(buf.as_ref(), b"Hello");
+    }
+
+    #[test]
+    fn fixture_is_mutable<T: AsRef<[u8]> + Extend<u8>>(#[fixture] mut buf: T) {
+        buf.extend(*b"!");
+        assert_eq!(buf.as_ref(), b"Hello!");
+    }
+
+    mod nested {
+        #[test]
+        fn fixture_in_submodule<T: AsRef<[u8]>>(#[fixture] buf: T) {
+            assert_eq!(buf.as_ref().len(), 5);
+        }
+    }
+
+    #[instantiate_tests(<Vec<u8>>, fixture = hello())]
+    mod vec {}
+
+    #[instantiate_tests(<BytesMut>, fixture = BytesMut::from(&hello()[..]))]
+    mod bytes_mut {}
+
+    #[instantiate_tests(<Vec<u8>>, fixture = make())]
+    mod vec_made_in_module {
+        fn make() -> Vec<u8> {
+            b"Hello".to_vec()
+        }
+    }
+}
+
+#[generic_tests::define(instantiate(
+    vec = <Vec<u8>> fixture = Vec::new(),
+    <String> fixture = String::new() cfg(all()),
+))]
+mod root_fixtures {
+    use std::fmt::Debug;
+
+    #[test]
+    fn is_default<T: Default + PartialEq + Debug>(#[fixture] value: T) {
+        assert_eq!(value, T::default());
+    }
+}
+
+#[generic_tests::define(attrs(allow))]
+mod fixture_between_args {
+    #[allow(dead_code)]
+    fn fixture_between<T>(a: i32, #[fixture] fixture: T, b: &str) {
+        let _ = (a, fixture, b);
+    }
+
+    #[deny(unused)]
+    #[instantiate_tests(<u32>, fixture = 42)]
+    mod inst {}
+}
+
+#[generic_tests::define]
+mod cases {
+    use std::fmt::Debug;
+    use std::str::FromStr;
+
+    const ANSWER: &str = "42";
+
+    #[test]
+    #[generic_test(case(1, "1"), case(42, ANSWER), case(-7, &format!("{}", -7)))]
+    fn parses<T: FromStr + PartialEq + Debug + From<i8>>(expected: i8, input: &str)
+    where
+        T::Err: Debug,
+    {
+        assert_eq!(input.parse::<T>().unwrap(), T::from(expected));
+    }
+
+    #[test]
+    #[generic_test(case(""), case("not a number"))]
+    #[should_panic]
+    fn rejects<T: FromStr>(input: &str)
+    where
+        T::Err: Debug,
+    {
+        input.parse::<T>().unwrap();
+    }
+
+    #[test]
+    #[generic_test(case(Vec::new()), case(vec![0; 3]))]
+    fn with_fixture<T: From<i8> + PartialEq + Debug>(#[fixture] zero: T, values: Vec<u8>) {
+        assert_eq!(zero, T::from(0));
+        assert!(values.iter().all(|&v| v == 0));
+    }
+
+    #[instantiate_tests(<i32>, fixture = 0)]
+    mod int {}
+
+    #[instantiate_tests(<i64>, fixture = 0)]
+    mod long {}
+}
+
 #[generic_tests::define]
 mod fallible_protocol {
     use std::borrow::Cow;
@@ -71,6 +1015,54 @@ mod fallible_protocol {
     mod cow {}
 }
 
+#[generic_tests::define]
+mod generics_in_signature {
+    use std::fmt::Debug;
+    use std::str::FromStr;
+
+    #[test]
+    fn parses_answer<T: FromStr>() -> Result<(), T::Err> {
+        "42".parse::<T>()?;
+        Ok(())
+    }
+
+    #[test]
+    fn parse_fails<T>() -> Result<(), <T as FromStr>::Err>
+    where
+        T: FromStr + Debug,
+    {
+        assert!("forty-two".parse::<T>().is_err());
+        Ok(())
+    }
+
+    #[instantiate_tests(<u8>)]
+    mod u8 {}
+
+    #[instantiate_tests(matrix(<u16 | u64>))]
+    mod matrix {}
+}
+
+#[generic_tests::define(attrs(allow))]
+mod generic_args_in_signature {
+    #[allow(dead_code)]
+    fn takes_value<T: Default + PartialEq>(v: T) -> bool {
+        v == T::default()
+    }
+
+    #[allow(dead_code)]
+    fn takes_array<T: Copy, const N: usize>(a: [T; N]) -> Option<T> {
+        a.first().copied()
+    }
+
+    #[allow(dead_code)]
+    fn returns_array<const N: usize>() -> [u8; N] {
+        [0; N]
+    }
+
+    #[instantiate_tests(<T = i32, N = 4>)]
+    mod i32_4 {}
+}
+
 #[generic_tests::define]
 mod nested {
     use std::fmt::{self, Display};
@@ -353,6 +1345,7 @@ mod fn_level_attr {
 
 #[generic_tests::define(attrs(allow))]
 #[allow(clippy::needless_lifetimes)]
+#[allow(mismatched_lifetime_syntaxes)]
 mod lifetimes_in_signature {
 
     struct Borrowed<'a> {
@@ -372,6 +1365,7 @@ mod lifetimes_in_signature {
     #[allow(dead_code)]
     fn two_args_sharing_a_lifetime<'b, T>(s: &'b str, mut b: Borrowed<'b>) {
         b.a = s;
+        let _ = b.a;
     }
 
     #[allow(dead_code)]
@@ -399,6 +1393,89 @@ mod lifetimes_in_signature {
         Borrowed { a: b }
     }
 
+    #[allow(dead_code)]
+    fn placeholder_in_input<T>(b: Borrowed<'_>) -> Borrowed<'_> {
+        b
+    }
+
+    #[allow(dead_code)]
+    fn two_placeholders_in_input<T>(_a: Borrowed<'_>, _b: &mut Borrowed<'_>) {}
+
+    #[allow(dead_code)]
+    fn bound_in_params<'a: 'b, 'b, T>(a: &'a str, b: &mut Borrowed<'b>) {
+        b.a = a;
+    }
+
+    #[allow(dead_code)]
+    fn bound_in_where_clause<'a, 'b, T>(a: &'a str, b: &mut Borrowed<'b>)
+    where
+        'a: 'b,
+    {
+        b.a = a;
+    }
+
+    #[allow(dead_code)]
+    fn bound_in_output<'a: 'b, 'b, T>(a: &'a str, _b: &'b str) -> Borrowed<'b> {
+        Borrowed { a }
+    }
+
+    #[allow(dead_code)]
+    fn static_bound<'a: 'static, T>(a: &'a str) -> Borrowed<'static> {
+        Borrowed { a }
+    }
+
+    #[allow(dead_code)]
+    fn bound_on_unused_lifetime<'a: 'b, 'b, T>(a: &'a str) -> Borrowed<'a> {
+        Borrowed { a }
+    }
+
+    #[instantiate_tests(<()>)]
+    mod inst {}
+}
+
+#[generic_tests::define(attrs(allow))]
+#[allow(clippy::toplevel_ref_arg)]
+mod arg_patterns {
+    struct Pair {
+        a: u32,
+        #[allow(dead_code)]
+        b: u32,
+    }
+
+    #[allow(dead_code)]
+    fn tuple<T>((a, b): (u32, u32)) -> u32 {
+        a + b
+    }
+
+    #[allow(dead_code)]
+    fn wildcard<T>(_: u32, _: &str) {}
+
+    #[allow(dead_code)]
+    fn by_ref<T>(ref s: String) -> usize {
+        s.len()
+    }
+
+    #[allow(dead_code)]
+    fn struct_pattern<T>(Pair { a, .. }: Pair) -> u32 {
+        a
+    }
+
+    #[allow(dead_code)]
+    fn reference<T>(&x: &u32) -> u32 {
+        x
+    }
+
+    #[allow(dead_code)]
+    fn slice<T>([first, _]: [u8; 2]) -> u8 {
+        first
+    }
+
+    #[allow(dead_code)]
+    fn mixed<T>(mut n: u32, (_, b): (u32, u32)) -> u32 {
+        n += b;
+        n
+    }
+
     #[instantiate_tests(<()>)]
     mod inst {}
 }